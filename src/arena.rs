@@ -1,12 +1,13 @@
+use std::cmp::min;
 use std::cmp::Ordering;
-use std::{cmp::max, cmp::min};
 use std::{
     fs::File,
     io::{Read, Write},
     path::PathBuf,
     println,
-    sync::mpsc::channel,
+    sync::mpsc::{channel, Sender},
     thread,
+    time::{Duration, Instant},
 };
 
 use itertools::Itertools;
@@ -19,7 +20,9 @@ use crate::{
     board::Coordinate,
     bots::{bot::Bot, bot::MoveRequest, replay_bot::ReplayBot},
     referee::{Player, RefereeState},
-    render::{InteractionMessage, MyApp, RenderMessage, RenderState},
+    render::{
+        FinalScore, InteractionMessage, MyApp, RenderMessage, RenderState, TimelineEntry, TurnStats,
+    },
     tile::{Rotation, TileClickTarget, TileData},
     tilebag::{LegalTileBag, ReplayTileBag, TileBag},
 };
@@ -48,6 +51,22 @@ impl Replay {
         serde_json::from_str(&json_string).or(Err("failed to deserialize file"))
     }
 
+    // the position before move `n`, with the tile of move `n` to place. Tiles that aren't
+    // on the board yet are assumed to still be in a standard bag, the next one is random
+    // once the replay runs out
+    pub fn state_at(&self, n: usize) -> MessageResult<RefereeState> {
+        let mut state =
+            RefereeState::from_players(self.turn_order.clone(), LegalTileBag::default().into());
+        for one_move in self.moves.iter().take(n) {
+            state.tilebag.rig(vec![one_move.tile_data.clone()]);
+            state.process_move(one_move.into())?;
+        }
+        if let Some(next) = self.moves.get(n) {
+            state.tilebag.rig(vec![next.tile_data.clone()]);
+        }
+        Ok(state)
+    }
+
     pub fn replay(&self, should_render: bool) -> GameResult {
         let n = self.turn_order.len();
         let mut bots: Vec<ReplayBot> = vec![];
@@ -83,31 +102,62 @@ impl Replay {
         out
     }
 
+    // the move list for the replay viewer, `frames` holding the state after each move
+    fn timeline(&self, frames: &[RenderState]) -> Vec<TimelineEntry> {
+        let no_scores = FxHashMap::default();
+        self.moves
+            .iter()
+            .zip(frames)
+            .enumerate()
+            .map(|(idx, (one_move, frame))| {
+                let before = match idx {
+                    0 => &no_scores,
+                    _ => &frames[idx - 1].player_scores,
+                };
+                let points = frame
+                    .player_scores
+                    .iter()
+                    .map(|(player, score)| {
+                        (player.clone(), score - before.get(player).unwrap_or(&0))
+                    })
+                    .filter(|(_, points)| *points > 0)
+                    .sorted()
+                    .collect();
+                TimelineEntry {
+                    player: self.turn_order[idx % self.turn_order.len()].clone(),
+                    tile: one_move.tile_data.code(),
+                    coord: one_move.coord,
+                    rotation: one_move.rotation.clone(),
+                    meeple: one_move.location.clone(),
+                    points,
+                }
+            })
+            .collect()
+    }
+
     fn replay_ui(&self, frames: Vec<RenderState>) {
         let (input_sender, input_receiver) = channel::<RenderMessage>();
         let (sender, receiver) = channel::<InteractionMessage>();
 
-        let mut frame_idx: isize = 0;
+        input_sender
+            .send(RenderMessage::Timeline(self.timeline(&frames)))
+            .unwrap();
+        for frame in &frames {
+            input_sender
+                .send(RenderMessage::Stats(TurnStats::new(frame)))
+                .unwrap();
+        }
+        let mut frame_idx = 0;
         let handle = thread::spawn(move || loop {
             input_sender
-                .send(RenderMessage::RefereeSync(
-                    frames[frame_idx as usize].clone(),
-                ))
+                .send(RenderMessage::RefereeSync(frames[frame_idx].clone()))
                 .unwrap();
-            match receiver.recv().unwrap() {
-                InteractionMessage::NextFrame => {
-                    frame_idx = min(frame_idx + 1, frames.len() as isize - 1);
-                }
-                InteractionMessage::PreviousFrame => {
-                    frame_idx = max(frame_idx - 1, 0);
-                }
-                InteractionMessage::LastFrame => {
-                    frame_idx = frames.len() as isize - 1;
-                }
-                InteractionMessage::FirstFrame => {
-                    frame_idx = 0;
-                }
-                _ => {}
+            // the window was closed
+            let Ok(message) = receiver.recv() else {
+                break;
+            };
+            if let InteractionMessage::ShowFrame(idx) = message {
+                frame_idx = min(idx, frames.len() - 1);
             }
         });
         let options = eframe::NativeOptions {
@@ -148,6 +198,17 @@ pub struct GameResult {
     pub player_scores: FxHashMap<Player, u32>,
 }
 
+// What happened over a game besides the final score
+#[derive(Clone, Debug, Default)]
+pub struct GameStats {
+    pub moves: u32,
+    /// Tiles drawn with no legal placement
+    pub discarded: u32,
+    pub duration: Duration,
+    /// Time each bot spent in `get_move`
+    pub think_time: FxHashMap<Player, Duration>,
+}
+
 impl GameResult {
     pub fn get_winners(&self) -> FxHashSet<Player> {
         let mut max_score: i32 = -1;
@@ -205,6 +266,22 @@ impl Match {
     pub fn play(bots: Vec<Box<dyn Bot>>, record: Option<PathBuf>) -> MessageResult<GameResult> {
         Self::play_custom(bots, LegalTileBag::default().into(), record, None, None)
     }
+    // a standard game that reports each move, its scoring and the final result to the GUI
+    pub fn play_logged(
+        bots: Vec<Box<dyn Bot>>,
+        record: Option<PathBuf>,
+        log: &Sender<RenderMessage>,
+    ) -> MessageResult<GameResult> {
+        Self::play_with_stats(
+            bots,
+            LegalTileBag::default().into(),
+            record,
+            None,
+            None,
+            Some(log),
+        )
+        .map(|(result, _)| result)
+    }
     pub fn play_custom(
         bots: Vec<Box<dyn Bot>>,
         bag: TileBagEnum,
@@ -212,6 +289,25 @@ impl Match {
         replay_frames: Option<&mut Vec<RenderState>>,
         referee_override: Option<RefereeState>,
     ) -> MessageResult<GameResult> {
+        Self::play_with_stats(bots, bag, record, replay_frames, referee_override, None)
+            .map(|(result, _)| result)
+    }
+    pub fn play_with_stats(
+        bots: Vec<Box<dyn Bot>>,
+        bag: TileBagEnum,
+        record: Option<PathBuf>,
+        replay_frames: Option<&mut Vec<RenderState>>,
+        referee_override: Option<RefereeState>,
+        log: Option<&Sender<RenderMessage>>,
+    ) -> MessageResult<(GameResult, GameStats)> {
+        // the GUI may have been closed, so failed sends are ignored
+        let send_log = |line: String| {
+            if let Some(log) = log {
+                log.send(RenderMessage::Log(line)).ok();
+            }
+        };
+        let start = Instant::now();
+        let mut stats = GameStats::default();
         let mut players: Vec<Player> = bots
             .iter()
             .map(|bot| bot.get_own_player().clone())
@@ -234,6 +330,10 @@ impl Match {
             turn_order: players.clone(),
             ..Default::default()
         };
+        if let Some(log) = log {
+            log.send(RenderMessage::RefereeSync(state.clone_into()))
+                .ok();
+        }
         let mut bypass = state.turn_idx;
         while state.tilebag.peek().is_ok() {
             for turn in &players {
@@ -241,12 +341,23 @@ impl Match {
                     bypass -= 1;
                     continue;
                 }
+                let remaining = state.tilebag.count_remaining();
                 let is_empty = !state.tilebag.ensure_legal_draw(&state.board.as_overlay());
+                let discarded = remaining - state.tilebag.count_remaining();
+                stats.discarded += discarded;
+                if discarded > 0 {
+                    send_log(format!(
+                        "Discarded {discarded} tile(s) with no legal placement"
+                    ));
+                }
                 if is_empty {
                     break;
                 }
                 let bot = player_map.get_mut(turn).unwrap();
+                let think_start = Instant::now();
                 let move_request = bot.get_move(&state);
+                *stats.think_time.entry(turn.clone()).or_default() += think_start.elapsed();
+                stats.moves += 1;
                 if record.is_some() {
                     if let Ok(tile) = state.tilebag.peek() {
                         replay_data.moves.push(ConcreteMove {
@@ -257,9 +368,24 @@ impl Match {
                         });
                     }
                 }
+                if log.is_some() {
+                    for line in Self::move_log(&state, &move_request, &bot.get_name())? {
+                        send_log(line);
+                    }
+                }
+                let coord = move_request.coord;
                 state.process_move(move_request)?;
+                if let Some(log) = log {
+                    let mut position = state.clone_into();
+                    log.send(RenderMessage::Stats(TurnStats::new(&position)))
+                        .ok();
+                    position.last_placed = Some(coord);
+                    log.send(RenderMessage::RefereeSync(position)).ok();
+                }
                 if let Some(&mut ref mut frames) = replay_frames {
-                    frames.push(state.clone_into());
+                    let mut frame = state.clone_into();
+                    frame.last_placed = Some(coord);
+                    frames.push(frame);
                 }
             }
         }
@@ -274,19 +400,60 @@ impl Match {
                 .or(Err("Failed to write to file"))?;
         }
 
-        let mut scores = state.board.as_overlay().get_standing_points();
-        for player in players {
-            let delta = state.player_scores.get(&player).unwrap_or(&0);
-            if let Some(score) = scores.get_mut(&player) {
-                *score += *delta
-            } else {
-                scores.insert(player, *delta);
-            }
+        if let Some(log) = log {
+            let standing = state.board.as_overlay().get_standing_points();
+            let results = players
+                .iter()
+                .map(|player| FinalScore {
+                    player: player.clone(),
+                    name: player_map[player].get_name(),
+                    banked: *state.player_scores.get(player).unwrap_or(&0),
+                    endgame: *standing.get(player).unwrap_or(&0),
+                })
+                .collect();
+            log.send(RenderMessage::GameOver(results)).ok();
         }
 
-        Ok(GameResult {
-            player_scores: scores,
-        })
+        stats.duration = start.elapsed();
+        Ok((
+            GameResult {
+                player_scores: state.get_final_scores(),
+            },
+            stats,
+        ))
+    }
+
+    // what `request` does, for the GUI log
+    fn move_log(
+        state: &RefereeState,
+        request: &MoveRequest,
+        name: &str,
+    ) -> MessageResult<Vec<String>> {
+        let player = state.get_player();
+        let meeple = request
+            .meeple
+            .as_ref()
+            .map(|target| format!(", meeple on {target:?}"))
+            .unwrap_or_default();
+        let mut lines = vec![format!(
+            "{player} ({name}) placed at {:?}{meeple}",
+            request.coord
+        )];
+        for feature in state.completed_features(request)? {
+            let scorers: Vec<String> = feature
+                .scoring_players
+                .iter()
+                .sorted()
+                .map(|player| player.to_string())
+                .collect();
+            lines.push(format!(
+                "  {} +{} for a {}",
+                scorers.join(" & "),
+                feature.points,
+                format!("{:?}", feature.feature).to_lowercase()
+            ));
+        }
+        Ok(lines)
     }
 }
 
@@ -320,5 +487,50 @@ mod tests {
         let replay = Replay::from_path(path).unwrap();
         let replay_result = replay.replay(false);
         assert_eq!(result, replay_result);
+
+        let n = replay.moves.len() / 2;
+        let state = replay.state_at(n).unwrap();
+        assert_eq!(state.board.as_overlay().tiles_placed() as usize, n);
+        assert!(state
+            .tilebag
+            .peek()
+            .unwrap()
+            .is_same_type(&replay.moves[n].tile_data));
+        let request: MoveRequest = (&replay.moves[n]).into();
+        assert!(state.clone().process_move(request).is_ok());
+        let end = replay.state_at(replay.moves.len()).unwrap();
+        assert_eq!(end.get_final_scores(), result.player_scores);
+    }
+
+    #[test]
+    fn logged_game_reports_results() {
+        let (sender, receiver) = channel::<RenderMessage>();
+        let bot_w: Box<dyn Bot> = Box::new(RandomBot::new(Player::White));
+        let bot_b: Box<dyn Bot> = Box::new(RandomBot::new(Player::Black));
+        let result = Match::play_logged(vec![bot_w, bot_b], None, &sender).unwrap();
+        drop(sender);
+
+        let mut placed = 0;
+        let mut turns = 0;
+        let mut positions = 0;
+        let mut results = None;
+        for message in receiver {
+            match message {
+                RenderMessage::Log(line) if line.contains("placed at") => placed += 1,
+                RenderMessage::Stats(_) => turns += 1,
+                RenderMessage::RefereeSync(_) => positions += 1,
+                RenderMessage::GameOver(scores) => results = Some(scores),
+                _ => {}
+            }
+        }
+        assert!(placed > 0);
+        assert_eq!(turns, placed);
+        assert_eq!(positions, placed + 1);
+        for score in results.unwrap() {
+            assert_eq!(
+                Some(&score.total()),
+                result.player_scores.get(&score.player)
+            );
+        }
     }
 }
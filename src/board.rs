@@ -5,17 +5,45 @@ use crate::{
     bots::bot::MoveRequest,
     coord_map::CoordMap,
     referee::Player,
-    tile::{MiniTile, Rotation, TileClickTarget, TileData, CARDINALS},
+    tile::{MiniTile, Rotation, TileClickTarget, TileData, CARDINALS, ROTATIONS},
 };
 use itertools::Itertools;
 use once_cell::sync::Lazy;
-use rustc_hash::{FxHashMap, FxHashSet};
+use rustc_hash::{FxHashMap, FxHashSet, FxHasher};
+use std::hash::{Hash, Hasher};
 
 pub type Coordinate = (i8, i8);
 
 #[derive(Clone, Default)]
 pub struct ConcreteBoard<T = CoordMap> {
     data: T,
+    // xor of `zobrist_key` over every placed tile, kept up to date by all mutations
+    hash: u64,
+}
+
+// splitmix64 finalizer, spreads the fx hash so xored keys don't cancel out
+pub(crate) fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+// Zobrist-style key of one placed tile: its coordinate, tile type and rotation, xored
+// with a key per meeple. Meeples are keyed in the tile's own frame
+fn zobrist_key(coord: &Coordinate, rotation: &Rotation, tile: &TileData) -> u64 {
+    let mut hasher = FxHasher::default();
+    coord.hash(&mut hasher);
+    tile.hash_type(&mut hasher);
+    rotation.hash(&mut hasher);
+    let mut key = mix(hasher.finish());
+    for (target, player) in &tile.meeple_locations {
+        let mut hasher = FxHasher::default();
+        coord.hash(&mut hasher);
+        target.hash(&mut hasher);
+        player.hash(&mut hasher);
+        key ^= mix(hasher.finish());
+    }
+    key
 }
 
 pub static DELTAS: [Coordinate; 4] = [(0, 1), (1, 0), (-1, 0), (0, -1)];
@@ -60,6 +88,7 @@ pub struct ScoringData {
     pub scoring_players: FxHashSet<Player>,
     pub points: u8,
     pub completed: bool,
+    pub feature: MiniTile,
     pub removal_candidate: FxHashSet<(Coordinate, TileClickTarget)>,
 }
 
@@ -79,7 +108,7 @@ pub struct OverlaidBoard<'a> {
 }
 
 impl BoardData for OverlaidBoard<'_> {
-    fn with_overlay<'a>(&'a self, coord: Coordinate, tile: &'a TileData) -> OverlaidBoard {
+    fn with_overlay<'a>(&'a self, coord: Coordinate, tile: &'a TileData) -> OverlaidBoard<'a> {
         let overlay = self.overlay.clone();
         if let Some(mut overlay) = overlay {
             overlay.insert(coord, tile);
@@ -103,7 +132,7 @@ impl BoardData for OverlaidBoard<'_> {
                 return Some(tile);
             }
         }
-        return self.inner_board.at(coord);
+        self.inner_board.at(coord)
     }
     fn tiles_present(&self) -> Box<dyn Iterator<Item = Coordinate> + '_> {
         if let Some(overlay) = &self.overlay {
@@ -142,6 +171,7 @@ impl FeatureResult<'_> {
         let (scoring_players, points) = self.get_score(is_endgame);
         ScoringData {
             completed: self.completed,
+            feature: self.feature.clone(),
             scoring_players,
             points,
             removal_candidate: self.get_removal_candidates(),
@@ -209,13 +239,15 @@ impl FeatureResult<'_> {
 pub trait BoardData {
     fn at(&self, coord: &Coordinate) -> Option<&TileData>;
     fn tiles_present(&self) -> Box<dyn Iterator<Item = Coordinate> + '_>;
-    fn with_overlay<'a>(&'a self, coord: Coordinate, tile: &'a TileData) -> OverlaidBoard;
+    fn with_overlay<'a>(&'a self, coord: Coordinate, tile: &'a TileData) -> OverlaidBoard<'a>;
 }
 
 // seems like this weirdo pattern is needed (?) b/c I want to return a reference to
 // board data (w/in feature result), so if this was just to be w/in board data
 // that would be a reference to self which is not object safe?
 
+type EarlyExitFn<T> = fn(&TileData, &Coordinate, &TileClickTarget) -> Option<T>;
+
 enum TraversalResult<'a, T> {
     FinalExit,
     EarlyExit(T),
@@ -230,7 +262,7 @@ impl OverlaidBoard<'_> {
         &self,
         initial_coord: &Coordinate,
         direction: TileClickTarget,
-        early_exit: Option<fn(&TileData, &Coordinate, &TileClickTarget) -> Option<T>>,
+        early_exit: Option<EarlyExitFn<T>>,
         should_build_result: bool,
     ) -> Option<TraversalResult<'_, T>> {
         let initial_tile = self.at(initial_coord)?;
@@ -314,7 +346,7 @@ impl OverlaidBoard<'_> {
         &self,
         initial_coord: &Coordinate,
         direction: TileClickTarget,
-    ) -> Option<FeatureResult> {
+    ) -> Option<FeatureResult<'_>> {
         let result = self.traverse_connecting_impl::<()>(initial_coord, direction, None, true)?;
         match result {
             TraversalResult::FinalExit | TraversalResult::EarlyExit(_) => {
@@ -378,7 +410,7 @@ impl OverlaidBoard<'_> {
         // prevent double reporting single tile
         let mut seen: FxHashSet<TileClickTarget> = FxHashSet::default();
         for direction in &CARDINALS {
-            if seen.get(direction).is_some() {
+            if seen.contains(direction) {
                 continue;
             }
             let feature_result = board.get_connecting_feature_results(coord, direction.clone());
@@ -418,7 +450,7 @@ impl OverlaidBoard<'_> {
         &self,
         coord: &Coordinate,
         direction: &TileClickTarget,
-    ) -> Option<FeatureResult> {
+    ) -> Option<FeatureResult<'_>> {
         let tile = self.at(coord)?;
         let feature = tile.at(direction);
 
@@ -469,7 +501,7 @@ impl OverlaidBoard<'_> {
         &self,
         initial_coord: &Coordinate,
         direction: &TileClickTarget,
-    ) -> Option<FeatureResult> {
+    ) -> Option<FeatureResult<'_>> {
         let feature = self.at(initial_coord).map(|tile| tile.at(direction))?;
         if feature != &MiniTile::Monastery {
             return None;
@@ -536,6 +568,54 @@ impl OverlaidBoard<'_> {
             }
         }
     }
+    // how many of `tiles`, kinds with their counts, would complete the feature at
+    // `coord`, `direction` in a single legal placement
+    pub fn count_completing(
+        &self,
+        coord: &Coordinate,
+        direction: &TileClickTarget,
+        tiles: &[(TileData, u32)],
+    ) -> u32 {
+        let Some(feature) = self.get_feature_result(coord, direction) else {
+            return 0;
+        };
+        if feature.completed {
+            return 0;
+        }
+        // monasteries also need their diagonals filled
+        let near: FxHashSet<Coordinate> = feature
+            .get_present_tiles()
+            .flat_map(|tile| {
+                OCTAL_DELTAS
+                    .iter()
+                    .map(move |delta| (tile.0 + delta.0, tile.1 + delta.1))
+            })
+            .collect();
+        let open: Vec<Coordinate> = self
+            .get_legal_tiles()
+            .into_iter()
+            .filter(|dest| near.contains(dest))
+            .collect();
+        tiles
+            .iter()
+            .filter(|(tile, _)| {
+                open.iter().any(|dest| {
+                    ROTATIONS.iter().any(|rotation| {
+                        let mut tile = tile.clone();
+                        tile.rotation = rotation.clone();
+                        self.is_features_match(dest, &tile)
+                            && self
+                                .with_overlay(*dest, &tile)
+                                .get_feature_result(coord, direction)
+                                .map(|result| result.completed)
+                                .unwrap_or(false)
+                    })
+                })
+            })
+            .map(|(_, count)| count)
+            .sum()
+    }
+
     pub fn does_legal_move_exist(&self, tile: &TileData) -> bool {
         if self.tiles_present().count() == 0 {
             return true;
@@ -678,7 +758,7 @@ impl BoardData for ConcreteBoard {
         Box::new(self.data.tiles_present())
     }
 
-    fn with_overlay<'a>(&'a self, coord: Coordinate, tile: &'a TileData) -> OverlaidBoard {
+    fn with_overlay<'a>(&'a self, coord: Coordinate, tile: &'a TileData) -> OverlaidBoard<'a> {
         let mut overlay = FxHashMap::default();
         overlay.insert(coord, tile);
         OverlaidBoard {
@@ -688,7 +768,104 @@ impl BoardData for ConcreteBoard {
     }
 }
 
+// Quarter turns clockwise of the whole board followed by a translation
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BoardSymmetry {
+    pub quarter_turns: u8,
+    pub offset: Coordinate,
+}
+
+impl BoardSymmetry {
+    fn turn(coord: &Coordinate, quarter_turns: u8) -> Coordinate {
+        let mut out = *coord;
+        for _ in 0..quarter_turns {
+            out = (out.1, -out.0);
+        }
+        out
+    }
+
+    pub fn apply(&self, coord: &Coordinate) -> Coordinate {
+        let turned = Self::turn(coord, self.quarter_turns);
+        (turned.0 - self.offset.0, turned.1 - self.offset.1)
+    }
+    pub fn invert(&self, coord: &Coordinate) -> Coordinate {
+        let shifted = (coord.0 + self.offset.0, coord.1 + self.offset.1);
+        Self::turn(&shifted, (4 - self.quarter_turns) % 4)
+    }
+
+    pub fn apply_rotation(&self, rotation: &Rotation) -> Rotation {
+        (0..self.quarter_turns).fold(rotation.clone(), |out, _| out.next_right())
+    }
+    pub fn invert_rotation(&self, rotation: &Rotation) -> Rotation {
+        (0..self.quarter_turns).fold(rotation.clone(), |out, _| out.next_left())
+    }
+
+    pub fn apply_target(&self, target: &TileClickTarget) -> TileClickTarget {
+        (0..self.quarter_turns).fold(target.clone(), |out, _| {
+            Rotation::Right.counter_rotate(&out)
+        })
+    }
+    pub fn invert_target(&self, target: &TileClickTarget) -> TileClickTarget {
+        (0..self.quarter_turns).fold(target.clone(), |out, _| Rotation::Right.rotate(&out))
+    }
+
+    pub fn apply_move(&self, request: &MoveRequest) -> MoveRequest {
+        MoveRequest {
+            coord: self.apply(&request.coord),
+            rotation: self.apply_rotation(&request.rotation),
+            meeple: request
+                .meeple
+                .as_ref()
+                .map(|target| self.apply_target(target)),
+        }
+    }
+    pub fn invert_move(&self, request: &MoveRequest) -> MoveRequest {
+        MoveRequest {
+            coord: self.invert(&request.coord),
+            rotation: self.invert_rotation(&request.rotation),
+            meeple: request
+                .meeple
+                .as_ref()
+                .map(|target| self.invert_target(target)),
+        }
+    }
+}
+
 impl ConcreteBoard {
+    pub fn zobrist(&self) -> u64 {
+        self.hash
+    }
+
+    // hash shared by every translation and global rotation of this board, along with
+    // the symmetry that maps this board onto the canonical one. It is the `zobrist` of
+    // the board after applying that symmetry
+    pub fn canonical_form(&self) -> (u64, BoardSymmetry) {
+        (0..4)
+            .map(|quarter_turns| {
+                let turned: Vec<(Coordinate, &TileData)> = self
+                    .data
+                    .iter()
+                    .map(|(coord, tile)| (BoardSymmetry::turn(&coord, quarter_turns), tile))
+                    .collect();
+                let offset = (
+                    turned.iter().map(|(coord, _)| coord.0).min().unwrap_or(0),
+                    turned.iter().map(|(coord, _)| coord.1).min().unwrap_or(0),
+                );
+                let symmetry = BoardSymmetry {
+                    quarter_turns,
+                    offset,
+                };
+
+                let hash = turned.into_iter().fold(0, |hash, (coord, tile)| {
+                    let coord = (coord.0 - offset.0, coord.1 - offset.1);
+                    hash ^ zobrist_key(&coord, &symmetry.apply_rotation(&tile.rotation), tile)
+                });
+                (hash, symmetry)
+            })
+            .min_by_key(|(hash, _)| *hash)
+            .unwrap()
+    }
+
     pub fn boundaries(&self) -> ((i8, i8), (i8, i8)) {
         let last = self.data.iter().last();
         if let Some(last) = last {
@@ -706,17 +883,33 @@ impl ConcreteBoard {
             ((1, -1), (1, -1))
         }
     }
-    pub fn at_mut(&mut self, coord: &Coordinate) -> Option<&mut TileData> {
-        self.data.get_mut(coord)
+    pub fn place_meeple(
+        &mut self,
+        coord: &Coordinate,
+        target: &TileClickTarget,
+        player: &Player,
+    ) -> MessageResult<()> {
+        let tile = self
+            .data
+            .get_mut(coord)
+            .ok_or("placing meeple on non existant tile")?;
+        let before = zobrist_key(coord, &tile.rotation, tile);
+        tile.place_meeple(target, player)?;
+        self.hash ^= before ^ zobrist_key(coord, &tile.rotation, tile);
+        Ok(())
     }
 
-    pub fn as_overlay(&self) -> OverlaidBoard {
+    pub fn as_overlay(&self) -> OverlaidBoard<'_> {
         OverlaidBoard {
             inner_board: self,
             overlay: None,
         }
     }
     pub fn set(&mut self, coord: Coordinate, tile: TileData) {
+        if let Some(old) = self.data.get(&coord) {
+            self.hash ^= zobrist_key(&coord, &old.rotation, old);
+        }
+        self.hash ^= zobrist_key(&coord, &tile.rotation, &tile);
         self.data.insert(coord, tile);
     }
 
@@ -732,7 +925,9 @@ impl ConcreteBoard {
         let mut return_meeples: FxHashMap<Player, u8> = FxHashMap::default();
         for (coord, target) in set {
             if let Some(tile) = self.data.get_mut(&coord) {
+                let before = zobrist_key(&coord, &tile.rotation, tile);
                 if let Some(player) = tile.clear_meeple(&target) {
+                    self.hash ^= before ^ zobrist_key(&coord, &tile.rotation, tile);
                     if let Some(output_count) = return_meeples.get_mut(&player) {
                         *output_count += 1;
                     } else {
@@ -750,6 +945,7 @@ mod tests {
     use std::assert_eq;
 
     use crate::{
+        bots::{bot::Bot, random_bot::RandomBot},
         referee::RefereeState,
         tile::{MiniTile, TileDataBuilder},
         tilebag::{LegalTileBag, ReplayTileBag, TileBag},
@@ -976,13 +1172,10 @@ mod tests {
         board.set((1, 0), tile_city);
         let player = Player::White;
 
-        let maybe_tile = board.at_mut(&(1, 0));
-        if let Some(tile) = maybe_tile {
-            let success = tile.place_meeple(&TileClickTarget::Top, &player);
-            assert!(success.is_ok());
-            let success = tile.place_meeple(&TileClickTarget::Left, &player);
-            assert!(success.is_ok());
-        }
+        let success = board.place_meeple(&(1, 0), &TileClickTarget::Top, &player);
+        assert!(success.is_ok());
+        let success = board.place_meeple(&(1, 0), &TileClickTarget::Left, &player);
+        assert!(success.is_ok());
 
         let tile_city: TileData = TileDataBuilder {
             bottom: MiniTile::City,
@@ -1032,13 +1225,10 @@ mod tests {
         tile_city.rotate_right();
         board.set((0, 0), tile_city.clone());
         let player = Player::White;
-        let maybe_tile = board.at_mut(&(0, 0));
-        if let Some(tile) = maybe_tile {
-            let success = tile.place_meeple(&TileClickTarget::Bottom, &player);
-            assert!(success.is_ok());
-            let success = tile.place_meeple(&TileClickTarget::Right, &player);
-            assert!(success.is_ok());
-        }
+        let success = board.place_meeple(&(0, 0), &TileClickTarget::Bottom, &player);
+        assert!(success.is_ok());
+        let success = board.place_meeple(&(0, 0), &TileClickTarget::Right, &player);
+        assert!(success.is_ok());
 
         let tile = board.at(&(0, 0)).unwrap();
         let completion = board.as_overlay().get_completion_points(&(0, 0), tile);
@@ -1148,4 +1338,132 @@ mod tests {
             })
             .unwrap();
     }
+
+    #[test]
+    fn counts_tiles_closing_a_city() {
+        let mut board = ConcreteBoard::default();
+        let cap: TileData = TileDataBuilder {
+            top: MiniTile::City,
+            ..Default::default()
+        }
+        .into();
+        let through: TileData = TileDataBuilder {
+            top: MiniTile::City,
+            center: MiniTile::City,
+            bottom: MiniTile::City,
+            ..Default::default()
+        }
+        .into();
+        let road: TileData = TileDataBuilder {
+            left: MiniTile::Road,
+            center: MiniTile::Road,
+            right: MiniTile::Road,
+            ..Default::default()
+        }
+        .into();
+        board.set((0, 0), cap.clone());
+        let bag = vec![(cap, 2), (through, 3), (road, 4)];
+
+        let overlay = board.as_overlay();
+        assert_eq!(
+            overlay.count_completing(&(0, 0), &TileClickTarget::Top, &bag),
+            2
+        );
+        assert_eq!(
+            overlay.count_completing(&(0, 0), &TileClickTarget::Left, &bag),
+            0
+        );
+    }
+
+    #[test]
+    fn zobrist_matches_rebuilt_board() {
+        let mut state = RefereeState::from_players(
+            vec![Player::White, Player::Black],
+            LegalTileBag::default().into(),
+        );
+        let mut bots = [RandomBot::new(Player::White), RandomBot::new(Player::Black)];
+        let mut seen = FxHashSet::default();
+        while state.tilebag.ensure_legal_draw(&state.board.as_overlay()) {
+            let request = bots[state.turn_idx].get_move(&state);
+            state.process_move(request).unwrap();
+
+            let mut rebuilt = ConcreteBoard::default();
+            for coord in state.board.as_overlay().tiles_present() {
+                rebuilt.set(coord, state.board.at(&coord).unwrap().clone());
+            }
+            assert_eq!(state.board.zobrist(), rebuilt.zobrist());
+            assert!(seen.insert(state.board.zobrist()));
+
+            // the canonical hash is the zobrist of the canonical board
+            let (hash, symmetry) = state.board.canonical_form();
+            let mut canonical = ConcreteBoard::default();
+            for coord in state.board.as_overlay().tiles_present() {
+                let mut tile = state.board.at(&coord).unwrap().clone();
+                tile.rotation = symmetry.apply_rotation(&tile.rotation);
+                canonical.set(symmetry.apply(&coord), tile);
+            }
+            assert_eq!(hash, canonical.zobrist());
+        }
+    }
+
+    #[test]
+    fn canonical_form_ignores_turns_and_shifts() {
+        let mut board = ConcreteBoard::default();
+        let mut city: TileData = TileDataBuilder {
+            top: MiniTile::City,
+            left: MiniTile::Road,
+            center: MiniTile::Road,
+            right: MiniTile::Road,
+            ..Default::default()
+        }
+        .into();
+        city.place_meeple(&TileClickTarget::Top, &Player::White)
+            .unwrap();
+        let mut road: TileData = TileDataBuilder {
+            left: MiniTile::Road,
+            center: MiniTile::Road,
+            right: MiniTile::Road,
+            ..Default::default()
+        }
+        .into();
+        road.rotation = Rotation::Right;
+        board.set((0, 0), city);
+        board.set((0, 1), road.clone());
+        board.set((-1, 1), road);
+
+        let shift = BoardSymmetry {
+            quarter_turns: 1,
+            offset: (3, -2),
+        };
+        let mut turned = ConcreteBoard::default();
+        for coord in board.as_overlay().tiles_present() {
+            let mut tile = board.at(&coord).unwrap().clone();
+            tile.rotation = shift.apply_rotation(&tile.rotation);
+            turned.set(shift.apply(&coord), tile);
+        }
+        assert_eq!(
+            turned
+                .at(&shift.apply(&(0, 0)))
+                .unwrap()
+                .get_meeple_at(&shift.apply_target(&TileClickTarget::Top)),
+            Some(Player::White)
+        );
+
+        let (hash, symmetry) = board.canonical_form();
+        let (turned_hash, turned_symmetry) = turned.canonical_form();
+        assert_eq!(hash, turned_hash);
+        assert_ne!(hash, ConcreteBoard::default().canonical_form().0);
+
+        let request = MoveRequest {
+            coord: (1, 1),
+            rotation: Rotation::Left,
+            meeple: Some(TileClickTarget::Right),
+        };
+        let canonical = symmetry.apply_move(&request);
+        assert_eq!(symmetry.invert_move(&canonical), request);
+        assert_eq!(
+            turned_symmetry.apply_move(&shift.apply_move(&request)),
+            canonical
+        );
+    }
 }
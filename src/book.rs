@@ -0,0 +1,281 @@
+use std::{
+    fs::{self, File},
+    hash::{Hash, Hasher},
+    io::{Read, Write},
+    path::PathBuf,
+};
+
+use rayon::prelude::*;
+use rustc_hash::{FxHashMap, FxHasher};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    arena::{MessageResult, Replay},
+    board::BoardSymmetry,
+    bots::{
+        bot::{MoveHint, MoveRequest},
+        registry::BotSpec,
+    },
+    referee::{Player, RefereeState},
+    tile::{Rotation, TileClickTarget, TileData},
+    tilebag::{LegalTileBag, ReplayTileBag, TileBag},
+};
+
+// A move as stored in the book, in the canonical frame of its position
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BookMove {
+    pub coord: (i8, i8),
+    pub rotation: Rotation,
+    pub meeple: Option<TileClickTarget>,
+    pub count: u32,
+}
+
+// Move frequencies for early positions, keyed by the canonical board hash plus
+// the tile to place, so transposed or turned openings share entries
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct OpeningBook {
+    pub max_plies: u32,
+    positions: FxHashMap<u64, Vec<BookMove>>,
+}
+
+impl OpeningBook {
+    pub fn new(max_plies: u32) -> Self {
+        Self {
+            max_plies,
+            positions: FxHashMap::default(),
+        }
+    }
+
+    pub fn from_path(input: PathBuf) -> MessageResult<Self> {
+        let file = File::open(input).or(Err("failed to open file"))?;
+        let mut buf_reader = std::io::BufReader::new(file);
+        let mut json_string = String::new();
+        buf_reader
+            .read_to_string(&mut json_string)
+            .or(Err("failed to read file"))?;
+        serde_json::from_str(&json_string).or(Err("failed to deserialize file"))
+    }
+
+    pub fn save(&self, path: PathBuf) -> MessageResult<()> {
+        let file = File::create(path).or(Err("Failed to create book file"))?;
+        let mut file_writer = std::io::BufWriter::new(file);
+        let json_string = serde_json::to_string(self).or(Err("Failed to serialize book"))?;
+        file_writer
+            .write_all(json_string.as_bytes())
+            .or(Err("Failed to write to file"))
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    fn key(state: &RefereeState) -> Option<(u64, BoardSymmetry)> {
+        let tile: &TileData = state.tilebag.peek().ok()?;
+        let (board_hash, symmetry) = state.board.canonical_form();
+        let mut hasher = FxHasher::default();
+        board_hash.hash(&mut hasher);
+        tile.hash_type(&mut hasher);
+        Some((hasher.finish(), symmetry))
+    }
+
+    fn in_range(&self, state: &RefereeState) -> bool {
+        state.board.as_overlay().tiles_placed() < self.max_plies
+    }
+
+    fn add_canonical(&mut self, key: u64, request: MoveRequest, count: u32) {
+        let moves = self.positions.entry(key).or_default();
+        if let Some(existing) = moves.iter_mut().find(|book_move| {
+            book_move.coord == request.coord
+                && book_move.rotation == request.rotation
+                && book_move.meeple == request.meeple
+        }) {
+            existing.count += count;
+        } else {
+            moves.push(BookMove {
+                coord: request.coord,
+                rotation: request.rotation,
+                meeple: request.meeple,
+                count,
+            });
+        }
+    }
+
+    fn canonical_entry(
+        &self,
+        state: &RefereeState,
+        request: &MoveRequest,
+    ) -> Option<(u64, MoveRequest)> {
+        if !self.in_range(state) {
+            return None;
+        }
+        let (key, symmetry) = Self::key(state)?;
+        Some((key, symmetry.apply_move(request)))
+    }
+
+    pub fn add(&mut self, state: &RefereeState, request: &MoveRequest) {
+        if let Some((key, canonical)) = self.canonical_entry(state, request) {
+            self.add_canonical(key, canonical, 1);
+        }
+    }
+
+    // legal book moves for this position in its own frame, most played first
+    pub fn lookup(&self, state: &RefereeState) -> Vec<(MoveRequest, u32)> {
+        if !self.in_range(state) {
+            return vec![];
+        }
+        let Some((key, symmetry)) = Self::key(state) else {
+            return vec![];
+        };
+        let Some(moves) = self.positions.get(&key) else {
+            return vec![];
+        };
+        let mut out: Vec<(MoveRequest, u32)> = moves
+            .iter()
+            .map(|book_move| {
+                let canonical = MoveRequest {
+                    coord: book_move.coord,
+                    rotation: book_move.rotation.clone(),
+                    meeple: book_move.meeple.clone(),
+                };
+                (symmetry.invert_move(&canonical), book_move.count)
+            })
+            .filter(|(request, _)| state.clone().process_move(request.clone()).is_ok())
+            .collect();
+        out.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        out
+    }
+
+    // book frequencies for the GUI, weighted by share of the position's games
+    pub fn hints(&self, state: &RefereeState) -> Vec<MoveHint> {
+        let moves = self.lookup(state);
+        let total: u32 = moves.iter().map(|(_, count)| count).sum();
+        moves
+            .into_iter()
+            .map(|(request, count)| {
+                let weight = count as f32 / total as f32;
+                MoveHint {
+                    request,
+                    label: format!("{:.0}%", weight * 100.0),
+                    weight,
+                }
+            })
+            .collect()
+    }
+
+    pub fn add_replay(&mut self, replay: &Replay) -> MessageResult<()> {
+        // a bag of no tiles can't be built, and there is nothing to learn anyway
+        if replay.moves.is_empty() {
+            return Ok(());
+        }
+        let tiles = replay
+            .moves
+            .iter()
+            .map(|one_move| one_move.tile_data.clone())
+            .collect();
+        let mut state =
+            RefereeState::from_players(replay.turn_order.clone(), ReplayTileBag::new(tiles).into());
+        for one_move in &replay.moves {
+            if !self.in_range(&state) {
+                break;
+            }
+            let request: MoveRequest = one_move.into();
+            self.add(&state, &request);
+            state.process_move(request)?;
+        }
+        Ok(())
+    }
+
+    // every `.replay` file directly inside `dir`
+    pub fn add_replay_dir(&mut self, dir: PathBuf) -> MessageResult<u32> {
+        let mut added = 0;
+        for entry in fs::read_dir(dir).or(Err("failed to read directory"))? {
+            let path = entry.or(Err("failed to read directory"))?.path();
+            if path.extension().map(|ext| ext == "replay").unwrap_or(false) {
+                self.add_replay(&Replay::from_path(path)?)?;
+                added += 1;
+            }
+        }
+        Ok(added)
+    }
+
+    // plays the opening of `games` games between two copies of `spec`
+    pub fn add_self_play(&mut self, spec: &BotSpec, games: u32) -> MessageResult<()> {
+        let entries: Vec<(u64, MoveRequest)> = (0..games)
+            .into_par_iter()
+            .map(|_| -> MessageResult<Vec<(u64, MoveRequest)>> {
+                let mut bots = [spec.build(Player::White)?, spec.build(Player::Black)?];
+                let mut state = RefereeState::from_players(
+                    vec![Player::White, Player::Black],
+                    LegalTileBag::default().into(),
+                );
+                let mut out = vec![];
+                while self.in_range(&state)
+                    && state.tilebag.ensure_legal_draw(&state.board.as_overlay())
+                {
+                    let request = bots[state.turn_idx].get_move(&state);
+                    out.extend(self.canonical_entry(&state, &request));
+                    state.process_move(request)?;
+                }
+                Ok(out)
+            })
+            .collect::<MessageResult<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .collect();
+        for (key, request) in entries {
+            self.add_canonical(key, request, 1);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::BoardData;
+
+    #[test]
+    fn lookup_survives_turned_board() {
+        let mut state = RefereeState::from_players(
+            vec![Player::White, Player::Black],
+            LegalTileBag::default().into(),
+        );
+        let opening = state.get_legal_moves()[0].clone();
+        state.process_move(opening).unwrap();
+        let request = state.get_legal_moves()[0].clone();
+
+        let mut book = OpeningBook::new(4);
+        book.add(&state, &request);
+        book.add(&state, &request);
+        assert_eq!(book.lookup(&state), vec![(request.clone(), 2)]);
+
+        // the same opening turned a quarter and shifted
+        let shift = BoardSymmetry {
+            quarter_turns: 1,
+            offset: (2, 5),
+        };
+        let mut turned = state.clone();
+        turned.board = Default::default();
+        for coord in state.board.as_overlay().tiles_present() {
+            let mut tile = state.board.at(&coord).unwrap().clone();
+            tile.rotation = shift.apply_rotation(&tile.rotation);
+            turned.board.set(shift.apply(&coord), tile);
+        }
+        assert_eq!(book.lookup(&turned), vec![(shift.apply_move(&request), 2)]);
+
+        let mut late = OpeningBook::new(1);
+        late.add(&state, &request);
+        assert!(late.is_empty());
+
+        let empty = Replay {
+            turn_order: vec![Player::White, Player::Black],
+            moves: vec![],
+        };
+        late.add_replay(&empty).unwrap();
+        assert!(late.is_empty());
+    }
+}
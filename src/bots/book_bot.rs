@@ -0,0 +1,64 @@
+use std::sync::Arc;
+
+use rand::{distributions::WeightedIndex, prelude::Distribution};
+
+use crate::{
+    book::OpeningBook,
+    referee::{Player, RefereeState},
+};
+
+use super::bot::{Analysis, Bot, CandidateMove, MoveRequest};
+
+// Plays from an opening book while the position is in it, otherwise defers to `inner`
+pub struct BookBot {
+    inner: Box<dyn Bot>,
+    book: Arc<OpeningBook>,
+}
+
+impl BookBot {
+    pub fn new(inner: Box<dyn Bot>, book: Arc<OpeningBook>) -> Self {
+        BookBot { inner, book }
+    }
+}
+
+impl Bot for BookBot {
+    fn get_name(&self) -> String {
+        format!("{} (book)", self.inner.get_name())
+    }
+
+    fn get_own_player(&self) -> &Player {
+        self.inner.get_own_player()
+    }
+
+    fn get_move(&mut self, state: &RefereeState) -> MoveRequest {
+        let moves = self.book.lookup(state);
+        if moves.is_empty() {
+            return self.inner.get_move(state);
+        }
+        // book moves are picked in proportion to how often they were played
+        let dist = WeightedIndex::new(moves.iter().map(|(_, count)| *count)).unwrap();
+        moves[dist.sample(&mut rand::thread_rng())].0.clone()
+    }
+
+    // book moves scored by their share of games, the inner bot's analysis out of book
+    fn analyze(&mut self, state: &RefereeState) -> Option<Analysis> {
+        let moves = self.book.lookup(state);
+        if moves.is_empty() {
+            return self.inner.analyze(state);
+        }
+        let total: u32 = moves.iter().map(|(_, count)| count).sum();
+        Some(Analysis {
+            principal_variation: vec![moves[0].0.clone()],
+            candidates: moves
+                .into_iter()
+                .map(|(request, count)| CandidateMove {
+                    request,
+                    score: count as f64 / total as f64,
+                    visits: Some(count as u64),
+                    expected_margin: None,
+                    std_error: None,
+                })
+                .collect(),
+        })
+    }
+}
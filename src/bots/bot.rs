@@ -8,6 +8,11 @@ pub trait Bot {
     fn get_own_player(&self) -> &Player;
     fn get_name(&self) -> String;
     fn get_move(&mut self, state: &RefereeState) -> MoveRequest;
+    // runs the bot's search and reports what it thought of each move, the first
+    // candidate being the one it would play. Bots without a search return None
+    fn analyze(&mut self, _state: &RefereeState) -> Option<Analysis> {
+        None
+    }
 }
 
 #[derive(Clone, PartialEq, Eq, Hash, Default, Debug)]
@@ -16,3 +21,140 @@ pub struct MoveRequest {
     pub rotation: Rotation,
     pub meeple: Option<TileClickTarget>,
 }
+
+// A suggested move shown over the board, e.g. an opening book frequency
+#[derive(Clone)]
+pub struct MoveHint {
+    pub request: MoveRequest,
+    pub label: String,
+    /// In [0, 1], sets how strongly the cell is highlighted
+    pub weight: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct CandidateMove {
+    pub request: MoveRequest,
+    /// In the bot's own units: average reward, heuristic points, book share...
+    pub score: f64,
+    pub visits: Option<u64>,
+    /// Expected final score margin for the moving player, when the bot estimates one
+    pub expected_margin: Option<f64>,
+    /// Standard error of `score` for sampling bots
+    pub std_error: Option<f64>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Analysis {
+    /// Best first
+    pub candidates: Vec<CandidateMove>,
+    /// Expected line of play starting with the best move, players alternating
+    pub principal_variation: Vec<MoveRequest>,
+}
+
+impl Analysis {
+    // ranks candidates by score, the best alone making up the principal variation
+    pub fn from_candidates(mut candidates: Vec<CandidateMove>) -> Self {
+        candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+        Analysis {
+            principal_variation: candidates
+                .iter()
+                .take(1)
+                .map(|c| c.request.clone())
+                .collect(),
+            candidates,
+        }
+    }
+
+    pub fn best(&self) -> Option<&MoveRequest> {
+        self.candidates.first().map(|candidate| &candidate.request)
+    }
+
+    // how much lower `request` scores than the best candidate, None if it wasn't considered.
+    // Score is what one-ply analyses are ranked by, so this is never negative for them
+    pub fn shortfall(&self, request: &MoveRequest) -> Option<f64> {
+        let best = self.candidates.first()?.score;
+        let candidate = self.candidates.iter().find(|c| &c.request == request)?;
+        Some(best - candidate.score)
+    }
+
+    // `marked` gets a `*` next to its rank
+    pub fn print(&self, limit: usize, marked: Option<&MoveRequest>) {
+        println!(
+            "{:>5} {:>9} {:>6} {:>7} {:>9} {:>7} {:>8} {:>8}",
+            "rank", "coord", "rot", "meeple", "score", "+/-", "visits", "margin"
+        );
+        for (rank, candidate) in self.candidates.iter().take(limit).enumerate() {
+            let request = &candidate.request;
+            let mark = if marked == Some(request) { "*" } else { " " };
+            println!(
+                "{mark}{:>4} {:>9} {:>6} {:>7} {:>9.2} {:>7} {:>8} {:>8}",
+                rank + 1,
+                format!("{:?}", request.coord),
+                format!("{:?}", request.rotation),
+                request
+                    .meeple
+                    .as_ref()
+                    .map(|target| format!("{target:?}"))
+                    .unwrap_or("-".to_owned()),
+                candidate.score,
+                candidate
+                    .std_error
+                    .map(|error| format!("{error:.2}"))
+                    .unwrap_or("-".to_owned()),
+                candidate
+                    .visits
+                    .map(|visits| visits.to_string())
+                    .unwrap_or("-".to_owned()),
+                candidate
+                    .expected_margin
+                    .map(|margin| format!("{margin:.1}"))
+                    .unwrap_or("-".to_owned()),
+            );
+        }
+        if self.candidates.len() > limit {
+            println!("  ... {} more", self.candidates.len() - limit);
+        }
+        if !self.principal_variation.is_empty() {
+            let line: Vec<String> = self
+                .principal_variation
+                .iter()
+                .map(|request| format!("{:?}/{:?}", request.coord, request.rotation))
+                .collect();
+            println!("pv: {}", line.join(" "));
+        }
+    }
+
+    // candidates for the GUI heatmap, weighted by share of visits when the bot spread
+    // them unevenly and by score relative to the best and worst candidate otherwise
+    pub fn hints(&self) -> Vec<MoveHint> {
+        let visits: Vec<u64> = self.candidates.iter().filter_map(|c| c.visits).collect();
+        let by_visits =
+            visits.len() == self.candidates.len() && visits.iter().min() != visits.iter().max();
+        let total_visits: u64 = visits.iter().sum();
+        let max = self
+            .candidates
+            .iter()
+            .map(|c| c.score)
+            .fold(f64::MIN, f64::max);
+        let min = self
+            .candidates
+            .iter()
+            .map(|c| c.score)
+            .fold(f64::MAX, f64::min);
+        self.candidates
+            .iter()
+            .map(|candidate| {
+                let weight = match candidate.visits {
+                    Some(visits) if by_visits => visits as f64 / total_visits as f64,
+                    _ if max > min => (candidate.score - min) / (max - min),
+                    _ => 1.0,
+                };
+                MoveHint {
+                    request: candidate.request.clone(),
+                    label: format!("{:.1}", candidate.score),
+                    weight: weight as f32,
+                }
+            })
+            .collect()
+    }
+}
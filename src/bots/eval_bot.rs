@@ -0,0 +1,87 @@
+use std::cmp::Ordering;
+
+use rand::rngs::ThreadRng;
+use rand::Rng;
+
+use crate::referee::{Player, RefereeState};
+
+use super::{
+    bot::{Analysis, Bot, CandidateMove, MoveRequest},
+    evaluator::Evaluator,
+};
+
+pub struct EvalBot {
+    pub own_player: Player,
+    rng: ThreadRng,
+    evaluator: Evaluator,
+}
+
+impl EvalBot {
+    pub fn new(player: Player) -> Self {
+        Self::with_evaluator(player, Evaluator::default())
+    }
+    pub fn with_evaluator(player: Player, evaluator: Evaluator) -> Self {
+        EvalBot {
+            own_player: player,
+            rng: rand::thread_rng(),
+            evaluator,
+        }
+    }
+}
+
+impl Bot for EvalBot {
+    fn get_name(&self) -> String {
+        "eval bot".to_owned()
+    }
+
+    fn get_own_player(&self) -> &Player {
+        &self.own_player
+    }
+
+    fn get_move(&mut self, state: &RefereeState) -> MoveRequest {
+        let moves: Vec<MoveRequest> = state.get_legal_moves();
+        let mut candidate: Option<(MoveRequest, f64)> = None;
+        for move_request in moves {
+            let mut next = state.clone();
+            next.process_move(move_request.clone()).unwrap();
+            let total = self.evaluator.evaluate(&next, self.get_own_player());
+
+            if let Some((_request, score)) = candidate.clone() {
+                match score.partial_cmp(&total) {
+                    Some(Ordering::Less) => {
+                        candidate = Some((move_request.clone(), total));
+                    }
+                    Some(Ordering::Equal) => {
+                        if self.rng.gen_bool(0.5) {
+                            candidate = Some((move_request.clone(), total));
+                        }
+                    }
+                    Some(Ordering::Greater) | None => {}
+                }
+            } else {
+                candidate = Some((move_request.clone(), total));
+            }
+        }
+        candidate.unwrap().0
+    }
+
+    fn analyze(&mut self, state: &RefereeState) -> Option<Analysis> {
+        let candidates: Vec<CandidateMove> = state
+            .get_legal_moves()
+            .into_iter()
+            .map(|request| {
+                let mut next = state.clone();
+                next.process_move(request.clone()).unwrap();
+                let value = self.evaluator.evaluate(&next, self.get_own_player());
+                CandidateMove {
+                    request,
+                    score: value,
+                    visits: None,
+                    expected_margin: Some(value),
+                    std_error: None,
+                }
+            })
+            .collect();
+        Some(Analysis::from_candidates(candidates))
+    }
+}
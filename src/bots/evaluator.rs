@@ -0,0 +1,225 @@
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::{
+    board::{BoardData, Coordinate, OverlaidBoard, ScoringData, OCTAL_DELTAS},
+    referee::{Player, RefereeState},
+    tile::{MiniTile, TileData, ROTATIONS},
+    tilebag::TileBag,
+};
+
+// Static estimate of a position, no playouts involved
+#[derive(Clone, Debug)]
+pub struct Evaluator {
+    /// Points a meeple in hand is worth at the start of the game, scaled down as the bag empties
+    pub meeple_value: f64,
+    /// Weight of the expected value of open features
+    pub feature_weight: f64,
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Self {
+            meeple_value: 4.0,
+            feature_weight: 1.0,
+        }
+    }
+}
+
+impl Evaluator {
+    // value of `player`'s position minus the value of everyone else's
+    pub fn evaluate(&self, state: &RefereeState, player: &Player) -> f64 {
+        let mut out = 0.0;
+        for (other, value) in self.player_values(state) {
+            if &other == player {
+                out += value;
+            } else {
+                out -= value;
+            }
+        }
+        out
+    }
+
+    pub fn player_values(&self, state: &RefereeState) -> FxHashMap<Player, f64> {
+        let board = state.board.as_overlay();
+        let bag = state.tilebag.get_type_counts();
+        let tiles_left = state.tilebag.count_remaining() as f64;
+        let tiles_placed = board.tiles_placed() as f64;
+        let game_remaining = if tiles_left + tiles_placed > 0.0 {
+            tiles_left / (tiles_left + tiles_placed)
+        } else {
+            0.0
+        };
+        let own_draws = tiles_left / state.turn_order.len() as f64;
+
+        let mut out: FxHashMap<Player, f64> = state
+            .turn_order
+            .iter()
+            .map(|player| {
+                let score = *state.player_scores.get(player).unwrap_or(&0) as f64;
+                let meeples = *state.player_meeples.get(player).unwrap_or(&0) as f64;
+                (
+                    player.clone(),
+                    score + self.meeple_value * meeples * game_remaining,
+                )
+            })
+            .collect();
+
+        for data in board.get_all_scoring_data() {
+            let value = self.feature_weight
+                * Self::expected_feature_value(&board, &data, &bag, tiles_left, own_draws);
+            for player in &data.scoring_players {
+                *out.entry(player.clone()).or_insert(0.0) += value;
+            }
+        }
+        out
+    }
+
+    // blend of what the feature scores if completed and what it scores at game end
+    pub fn expected_feature_value(
+        board: &OverlaidBoard,
+        data: &ScoringData,
+        bag: &[(TileData, u32)],
+        tiles_left: f64,
+        draws: f64,
+    ) -> f64 {
+        let endgame_value = data.points as f64;
+        if data.completed {
+            return endgame_value;
+        }
+        let completed_value = match data.feature {
+            MiniTile::City => 2.0 * endgame_value,
+            MiniTile::Monastery => 9.0,
+            MiniTile::Road | MiniTile::Grass | MiniTile::Junction => endgame_value,
+        };
+        let p = Self::completion_probability(board, data, bag, tiles_left, draws);
+        p * completed_value + (1.0 - p) * endgame_value
+    }
+
+    // chance that every hole in the feature gets filled, treating each hole independently
+    pub fn completion_probability(
+        board: &OverlaidBoard,
+        data: &ScoringData,
+        bag: &[(TileData, u32)],
+        tiles_left: f64,
+        draws: f64,
+    ) -> f64 {
+        let open = Self::open_coords(board, data);
+        if open.is_empty() {
+            return 1.0;
+        }
+        if tiles_left <= 0.0 {
+            return 0.0;
+        }
+        open.iter()
+            .map(|coord| {
+                let fitting: u32 = bag
+                    .iter()
+                    .filter(|(tile, _)| Self::fits(board, coord, tile))
+                    .map(|(_, count)| count)
+                    .sum();
+                let miss = 1.0 - fitting as f64 / tiles_left;
+                1.0 - miss.powf(draws.max(1.0))
+            })
+            .product()
+    }
+
+    pub fn open_coords(board: &OverlaidBoard, data: &ScoringData) -> FxHashSet<Coordinate> {
+        match data.feature {
+            MiniTile::Monastery => data
+                .removal_candidate
+                .iter()
+                .flat_map(|(center, _)| {
+                    OCTAL_DELTAS
+                        .iter()
+                        .map(move |delta| (center.0 + delta.0, center.1 + delta.1))
+                })
+                .filter(|coord| board.at(coord).is_none())
+                .collect(),
+            _ => data
+                .removal_candidate
+                .iter()
+                .map(|(coord, _)| *coord)
+                .filter(|coord| board.at(coord).is_none())
+                .collect(),
+        }
+    }
+
+    fn fits(board: &OverlaidBoard, coord: &Coordinate, tile: &TileData) -> bool {
+        ROTATIONS.iter().any(|rotation| {
+            let mut tile = tile.clone();
+            tile.rotation = rotation.clone();
+            board.is_features_match(coord, &tile)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        board::ConcreteBoard,
+        tile::{TileClickTarget, TileDataBuilder},
+        tilebag::{LegalTileBag, ReplayTileBag},
+    };
+
+    fn open_city_state(bag_tiles: Vec<TileData>) -> RefereeState {
+        let mut board = ConcreteBoard::default();
+        let mut tile: TileData = TileDataBuilder {
+            top: MiniTile::City,
+            ..Default::default()
+        }
+        .into();
+        tile.place_meeple(&TileClickTarget::Top, &Player::White)
+            .unwrap();
+        board.set((0, 0), tile);
+        let mut state = RefereeState::from_players(
+            vec![Player::White, Player::Black],
+            ReplayTileBag::new(bag_tiles).into(),
+        );
+        state.board = board;
+        state
+    }
+
+    #[test]
+    fn open_city_favors_owner() {
+        let state = open_city_state(vec![TileDataBuilder::default().into()]);
+        let evaluator = Evaluator::default();
+        let white = evaluator.evaluate(&state, &Player::White);
+        assert!(white > 0.0);
+        assert_eq!(white, -evaluator.evaluate(&state, &Player::Black));
+    }
+
+    #[test]
+    fn completion_depends_on_bag() {
+        let closer: TileData = TileDataBuilder {
+            bottom: MiniTile::City,
+            ..Default::default()
+        }
+        .into();
+        let hopeless = open_city_state(vec![TileDataBuilder::default().into()]);
+        let hopeful = open_city_state(vec![closer]);
+
+        let probability = |state: &RefereeState| {
+            let board = state.board.as_overlay();
+            let data = &board.get_all_scoring_data()[0];
+            let bag = state.tilebag.get_type_counts();
+            Evaluator::completion_probability(&board, data, &bag, 1.0, 1.0)
+        };
+        assert_eq!(probability(&hopeless), 0.0);
+        assert_eq!(probability(&hopeful), 1.0);
+    }
+
+    #[test]
+    fn meeples_in_hand_count() {
+        let evaluator = Evaluator::default();
+        let mut state = RefereeState::from_players(
+            vec![Player::White, Player::Black],
+            LegalTileBag::default().into(),
+        );
+        state.player_meeples.insert(Player::Black, 6);
+        assert_eq!(
+            evaluator.evaluate(&state, &Player::White),
+            evaluator.meeple_value
+        );
+    }
+}
@@ -0,0 +1,455 @@
+use std::{
+    cell::{Cell, RefCell},
+    cmp::Reverse,
+};
+
+use crate::{
+    referee::{Player, RefereeState},
+    tile::TileData,
+    tilebag::TileBag,
+};
+
+use super::{
+    bot::{Analysis, Bot, CandidateMove, MoveRequest},
+    evaluator::Evaluator,
+    greedy_bot::GreedyBot,
+    mcts_bot::score_margin,
+    transposition::TranspositionTable,
+};
+
+pub const TABLE_SIZE: usize = 1 << 16;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Bound {
+    Exact,
+    /// The search failed high, the true value is at least this
+    Lower,
+    /// The search failed low, the true value is at most this
+    Upper,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct TableEntry {
+    depth: u32,
+    value: f64,
+    bound: Bound,
+}
+
+// Depth limited expectimax, decision nodes alternate with chance nodes over the
+// kinds of tile left in the bag. Chance nodes are pruned with Ballard's star1/star2.
+pub struct ExpectimaxBot {
+    pub own_player: Player,
+    depth: u32,
+    evaluator: Evaluator,
+    /// Evaluations are clamped to +/- this, star1 needs bounded values
+    pub value_bound: f64,
+    pub pruning: bool,
+    /// Probe the first move of each chance child for star2 cutoffs, star1 only if unset
+    pub probing: bool,
+    /// Reuse values of decision nodes reached through different move orders
+    pub transpositions: bool,
+    table: RefCell<TranspositionTable<TableEntry>>,
+    /// Decision nodes searched, probes included
+    nodes: Cell<u64>,
+}
+
+impl ExpectimaxBot {
+    pub fn new(player: Player, depth: u32) -> Self {
+        ExpectimaxBot {
+            own_player: player,
+            depth: depth.max(1),
+            evaluator: Evaluator::default(),
+            value_bound: 200.0,
+            pruning: true,
+            probing: true,
+            transpositions: true,
+            table: RefCell::new(TranspositionTable::new(TABLE_SIZE)),
+            nodes: Cell::new(0),
+        }
+    }
+
+    // best move at the root and its expected value for the own player
+    pub fn search(&self, state: &RefereeState) -> (MoveRequest, f64) {
+        self.table.borrow_mut().new_search();
+        let upper = self.value_bound;
+        let mut alpha = -self.value_bound;
+        let mut best: Option<(MoveRequest, f64)> = None;
+        for move_request in Self::ordered_moves(state) {
+            let mut next = state.clone();
+            next.process_move(move_request.clone()).unwrap();
+            let window = if self.pruning {
+                (alpha, upper)
+            } else {
+                (-self.value_bound, upper)
+            };
+            let value = self.chance(&next, self.depth - 1, window.0, window.1);
+            if best.as_ref().map(|(_, v)| value > *v).unwrap_or(true) {
+                alpha = alpha.max(value);
+                best = Some((move_request, value));
+            }
+        }
+        best.unwrap()
+    }
+
+    fn ordered_moves(state: &RefereeState) -> Vec<MoveRequest> {
+        let board = state.board.as_overlay();
+        let tile = state.tilebag.peek().unwrap();
+        let player = state.get_player();
+        let mut moves: Vec<(i32, MoveRequest)> = state
+            .get_legal_moves()
+            .into_iter()
+            .map(|request| {
+                (
+                    GreedyBot::score_move(&board, tile, &request, &player),
+                    request,
+                )
+            })
+            .collect();
+        moves.sort_by_key(|(score, _)| Reverse(*score));
+        moves.into_iter().map(|(_, request)| request).collect()
+    }
+
+    fn leaf(&self, state: &RefereeState) -> f64 {
+        let value = self.evaluator.evaluate(state, &self.own_player);
+        value.clamp(-self.value_bound, self.value_bound)
+    }
+
+    fn game_over(&self, state: &RefereeState) -> f64 {
+        let value = score_margin(&state.get_final_scores(), &self.own_player);
+        value.clamp(-self.value_bound, self.value_bound)
+    }
+
+    // drawable tile kinds with their probability, unplayable tiles get discarded so are skipped
+    fn outcomes(state: &RefereeState) -> Vec<(TileData, f64)> {
+        let board = state.board.as_overlay();
+        let legal: Vec<(TileData, u32)> = state
+            .tilebag
+            .get_type_counts()
+            .into_iter()
+            .filter(|(tile, _)| board.does_legal_move_exist(tile))
+            .collect();
+        let total: u32 = legal.iter().map(|(_, count)| count).sum();
+        legal
+            .into_iter()
+            .map(|(tile, count)| (tile, count as f64 / total as f64))
+            .collect()
+    }
+
+    // stored bounds on a decision node's value, if searched at least this deep
+    fn stored_bounds(&self, state: &RefereeState, depth: u32) -> Option<(f64, f64)> {
+        if !self.transpositions {
+            return None;
+        }
+        let entry = self.table.borrow().get(state.decision_hash()).copied()?;
+        if entry.depth < depth {
+            return None;
+        }
+        Some(match entry.bound {
+            Bound::Exact => (entry.value, entry.value),
+            Bound::Lower => (entry.value, self.value_bound),
+            Bound::Upper => (-self.value_bound, entry.value),
+        })
+    }
+
+    fn decide(&self, state: &RefereeState, depth: u32, mut alpha: f64, mut beta: f64) -> f64 {
+        self.nodes.set(self.nodes.get() + 1);
+        let key = self.transpositions.then(|| state.decision_hash());
+        if let Some(entry) = key.and_then(|key| self.table.borrow().get(key).copied()) {
+            if entry.depth >= depth {
+                match entry.bound {
+                    Bound::Exact => return entry.value,
+                    Bound::Lower if entry.value >= beta => return entry.value,
+                    Bound::Upper if entry.value <= alpha => return entry.value,
+                    _ => {}
+                }
+            }
+        }
+        let window = (alpha, beta);
+
+        let maximizing = state.get_player() == self.own_player;
+        let mut best = if maximizing {
+            -self.value_bound
+        } else {
+            self.value_bound
+        };
+        for move_request in Self::ordered_moves(state) {
+            let mut next = state.clone();
+            next.process_move(move_request).unwrap();
+            let value = if self.pruning {
+                self.chance(&next, depth - 1, alpha, beta)
+            } else {
+                self.chance(&next, depth - 1, -self.value_bound, self.value_bound)
+            };
+            if maximizing {
+                best = best.max(value);
+                alpha = alpha.max(best);
+            } else {
+                best = best.min(value);
+                beta = beta.min(best);
+            }
+            if self.pruning && alpha >= beta {
+                break;
+            }
+        }
+        if let Some(key) = key {
+            let bound = if best <= window.0 {
+                Bound::Upper
+            } else if best >= window.1 {
+                Bound::Lower
+            } else {
+                Bound::Exact
+            };
+            let entry = TableEntry {
+                depth,
+                value: best,
+                bound,
+            };
+            self.table.borrow_mut().insert(key, depth, entry);
+        }
+        best
+    }
+
+    // searches only the first ordered move of a decision node in the window, its value
+    // bounds the node from below for a max node and from above for a min node
+    fn probe(&self, state: &RefereeState, depth: u32, alpha: f64, beta: f64) -> f64 {
+        self.nodes.set(self.nodes.get() + 1);
+        let move_request = Self::ordered_moves(state).swap_remove(0);
+        let mut next = state.clone();
+        next.process_move(move_request).unwrap();
+        self.chance(&next, depth - 1, alpha, beta)
+    }
+
+    fn chance(&self, state: &RefereeState, depth: u32, alpha: f64, beta: f64) -> f64 {
+        if depth == 0 {
+            return self.leaf(state);
+        }
+        let outcomes = Self::outcomes(state);
+        if outcomes.is_empty() {
+            return self.game_over(state);
+        }
+        let children: Vec<(RefereeState, f64)> = outcomes
+            .into_iter()
+            .map(|(tile, p)| {
+                let mut child = state.clone();
+                child.tilebag.rig(vec![tile]);
+                (child, p)
+            })
+            .collect();
+
+        if !self.pruning {
+            return children
+                .iter()
+                .map(|(child, p)| {
+                    p * self.decide(child, depth, -self.value_bound, self.value_bound)
+                })
+                .sum();
+        }
+
+        // start from whatever earlier searches stored about the children
+        let (mut lower, mut upper): (Vec<f64>, Vec<f64>) = children
+            .iter()
+            .map(|(child, _)| {
+                self.stored_bounds(child, depth)
+                    .unwrap_or((-self.value_bound, self.value_bound))
+            })
+            .unzip();
+        let weighted =
+            |bounds: &[f64]| -> f64 { children.iter().zip(bounds).map(|((_, p), v)| p * v).sum() };
+
+        // star2: searching just each child's first move tightens its bound on one side,
+        // cutting the node once the bounds leave the window
+        let next_is_max = state.get_player() == self.own_player;
+        for (i, (child, p)) in children.iter().enumerate() {
+            let (lower_sum, upper_sum) = (weighted(&lower), weighted(&upper));
+            if lower_sum >= beta {
+                return lower_sum;
+            }
+            if upper_sum <= alpha {
+                return upper_sum;
+            }
+            if !self.probing {
+                break;
+            }
+            // the probe only has to settle the value between what would cut the
+            // node and what star1 would give up on for this child
+            let child_alpha = (alpha - upper_sum) / p + upper[i];
+            let child_beta = (beta - lower_sum) / p + lower[i];
+            let window = (child_alpha.max(lower[i]), child_beta.min(upper[i]));
+            if window.0 >= window.1 {
+                // already settled by a stored exact value
+                continue;
+            }
+            let value = self.probe(child, depth, window.0, window.1);
+            if next_is_max && value > window.0 {
+                lower[i] = lower[i].max(value);
+            }
+            if !next_is_max && value < window.1 {
+                upper[i] = upper[i].min(value);
+            }
+        }
+        let (lower_sum, upper_sum) = (weighted(&lower), weighted(&upper));
+        if lower_sum >= beta {
+            return lower_sum;
+        }
+        if upper_sum <= alpha {
+            return upper_sum;
+        }
+
+        // star1: narrow each child's window using what the others can still contribute
+        let mut known = 0.0;
+        for (i, (child, p)) in children.iter().enumerate() {
+            let rest_lower: f64 = children[i + 1..]
+                .iter()
+                .zip(&lower[i + 1..])
+                .map(|((_, p), v)| p * v)
+                .sum();
+            let rest_upper: f64 = children[i + 1..]
+                .iter()
+                .zip(&upper[i + 1..])
+                .map(|((_, p), v)| p * v)
+                .sum();
+            let child_alpha = (alpha - known - rest_upper) / p;
+            let child_beta = (beta - known - rest_lower) / p;
+            if lower[i] >= child_beta {
+                return known + p * lower[i] + rest_lower;
+            }
+            if upper[i] <= child_alpha {
+                return known + p * upper[i] + rest_upper;
+            }
+
+            let value = self.decide(
+                child,
+                depth,
+                child_alpha.max(lower[i]),
+                child_beta.min(upper[i]),
+            );
+            known += p * value;
+            if value >= child_beta {
+                return known + rest_lower;
+            }
+            if value <= child_alpha {
+                return known + rest_upper;
+            }
+        }
+        known
+    }
+}
+
+impl Bot for ExpectimaxBot {
+    fn get_name(&self) -> String {
+        format!("expectimax bot {}", self.depth)
+    }
+
+    fn get_own_player(&self) -> &Player {
+        &self.own_player
+    }
+
+    fn get_move(&mut self, state: &RefereeState) -> MoveRequest {
+        self.search(state).0
+    }
+
+    // every root move searched with a full window, so values are exact rather than bounds
+    fn analyze(&mut self, state: &RefereeState) -> Option<Analysis> {
+        self.table.borrow_mut().new_search();
+        let candidates: Vec<CandidateMove> = Self::ordered_moves(state)
+            .into_iter()
+            .map(|request| {
+                let mut next = state.clone();
+                next.process_move(request.clone()).unwrap();
+                let value = self.chance(&next, self.depth - 1, -self.value_bound, self.value_bound);
+                CandidateMove {
+                    request,
+                    score: value,
+                    visits: None,
+                    expected_margin: Some(value),
+                    std_error: None,
+                }
+            })
+            .collect();
+        Some(Analysis::from_candidates(candidates))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tile::{MiniTile, TileDataBuilder},
+        tilebag::ReplayTileBag,
+    };
+
+    fn small_game() -> RefereeState {
+        let straight: TileData = TileDataBuilder {
+            left: MiniTile::Road,
+            center: MiniTile::Road,
+            right: MiniTile::Road,
+            ..Default::default()
+        }
+        .into();
+        let city: TileData = TileDataBuilder {
+            top: MiniTile::City,
+            ..Default::default()
+        }
+        .into();
+        let end: TileData = TileDataBuilder {
+            left: MiniTile::Road,
+            center: MiniTile::Junction,
+            ..Default::default()
+        }
+        .into();
+        let bag = ReplayTileBag::new(vec![
+            straight.clone(),
+            city.clone(),
+            end.clone(),
+            straight,
+            city,
+            end,
+        ]);
+        let mut state = RefereeState::from_players(vec![Player::White, Player::Black], bag.into());
+        state
+            .process_move(MoveRequest {
+                coord: (0, 0),
+                ..Default::default()
+            })
+            .unwrap();
+        state
+    }
+
+    #[test]
+    fn pruning_keeps_value() {
+        let state = small_game();
+        let mut pruned = ExpectimaxBot::new(Player::Black, 3);
+        let (request, pruned_value) = pruned.search(&state);
+        pruned.pruning = false;
+        let (_, full_value) = pruned.search(&state);
+
+        assert!((pruned_value - full_value).abs() < 1e-9);
+        assert!(state.get_legal_moves().contains(&request));
+    }
+
+    #[test]
+    fn probing_saves_nodes() {
+        let state = small_game();
+        let star2 = ExpectimaxBot::new(Player::Black, 4);
+        let mut star1 = ExpectimaxBot::new(Player::Black, 4);
+        star1.probing = false;
+
+        let (_, star2_value) = star2.search(&state);
+        let (_, star1_value) = star1.search(&state);
+        assert!((star2_value - star1_value).abs() < 1e-9);
+        assert!(star2.nodes.get() < star1.nodes.get());
+    }
+
+    #[test]
+    fn transpositions_keep_value() {
+        let state = small_game();
+        let with_table = ExpectimaxBot::new(Player::Black, 4);
+        let mut without_table = ExpectimaxBot::new(Player::Black, 4);
+        without_table.transpositions = false;
+
+        let (_, table_value) = with_table.search(&state);
+        let (_, plain_value) = without_table.search(&state);
+        assert!((table_value - plain_value).abs() < 1e-9);
+        assert!(!with_table.table.borrow().is_empty());
+    }
+}
@@ -0,0 +1,42 @@
+use crate::referee::{Player, RefereeState};
+
+use super::bot::{Analysis, Bot, MoveRequest};
+
+// Prints the analysis behind each of `inner`'s moves before playing it
+pub struct ExplainBot {
+    inner: Box<dyn Bot>,
+    /// Candidates printed per move
+    pub limit: usize,
+}
+
+impl ExplainBot {
+    pub fn new(inner: Box<dyn Bot>) -> Self {
+        ExplainBot { inner, limit: 10 }
+    }
+}
+
+impl Bot for ExplainBot {
+    fn get_name(&self) -> String {
+        self.inner.get_name()
+    }
+
+    fn get_own_player(&self) -> &Player {
+        self.inner.get_own_player()
+    }
+
+    fn get_move(&mut self, state: &RefereeState) -> MoveRequest {
+        let Some(analysis) = self.inner.analyze(state) else {
+            return self.inner.get_move(state);
+        };
+        println!("{} ({}) analysis:", self.get_name(), self.get_own_player());
+        analysis.print(self.limit, None);
+        match analysis.best() {
+            Some(best) => best.clone(),
+            None => self.inner.get_move(state),
+        }
+    }
+
+    fn analyze(&mut self, state: &RefereeState) -> Option<Analysis> {
+        self.inner.analyze(state)
+    }
+}
@@ -4,9 +4,13 @@ use std::cmp::Ordering;
 use rand::rngs::ThreadRng;
 use rand::Rng;
 
-use crate::referee::{Player, RefereeState};
+use crate::{
+    board::OverlaidBoard,
+    referee::{Player, RefereeState},
+    tile::TileData,
+};
 
-use super::bot::{Bot, MoveRequest};
+use super::bot::{Analysis, Bot, CandidateMove, MoveRequest};
 
 pub struct GreedyBot {
     pub own_player: Player,
@@ -20,6 +24,32 @@ impl GreedyBot {
             rng: rand::thread_rng(),
         }
     }
+
+    // points completed by this move for `player` minus those completed for anyone else
+    pub fn score_move(
+        board_user: &OverlaidBoard,
+        tile: &TileData,
+        move_request: &MoveRequest,
+        player: &Player,
+    ) -> i32 {
+        let mut tile = tile.clone();
+        if let Some(location) = &move_request.meeple {
+            tile.place_meeple(location, player).unwrap();
+        }
+        tile.rotation = move_request.rotation.clone();
+        let points = board_user.get_completion_points(&move_request.coord, &tile);
+        let mut total: i32 = 0;
+        for (scorer, points) in points {
+            if let Some(scorer) = scorer {
+                if &scorer == player {
+                    total += points as i32;
+                } else {
+                    total -= points as i32;
+                }
+            }
+        }
+        total
+    }
 }
 
 impl Bot for GreedyBot {
@@ -38,22 +68,7 @@ impl Bot for GreedyBot {
         let moves: Vec<MoveRequest> = state.get_legal_moves();
         let mut candidate: Option<(MoveRequest, i32)> = None;
         for move_request in moves {
-            let mut tile = tile.clone();
-            if let Some(location) = &move_request.meeple {
-                tile.place_meeple(location, self.get_own_player()).unwrap();
-            }
-            tile.rotation = move_request.rotation.clone();
-            let points = board_user.get_completion_points(&move_request.coord, &tile);
-            let mut total: i32 = 0;
-            for (player, points) in points {
-                if let Some(player) = player {
-                    if &player == self.get_own_player() {
-                        total += points as i32;
-                    } else {
-                        total -= points as i32;
-                    }
-                }
-            }
+            let total = Self::score_move(&board_user, tile, &move_request, self.get_own_player());
             if let Some((_request, score)) = candidate.clone() {
                 match score.cmp(&total) {
                     Ordering::Less => {
@@ -72,6 +87,23 @@ impl Bot for GreedyBot {
         }
         candidate.unwrap().0
     }
+
+    fn analyze(&mut self, state: &RefereeState) -> Option<Analysis> {
+        let board_user = state.board.as_overlay();
+        let tile = state.tilebag.peek().ok()?;
+        let candidates: Vec<CandidateMove> = state
+            .get_legal_moves()
+            .into_iter()
+            .map(|request| CandidateMove {
+                score: Self::score_move(&board_user, tile, &request, self.get_own_player()) as f64,
+                request,
+                visits: None,
+                expected_margin: None,
+                std_error: None,
+            })
+            .collect();
+        Some(Analysis::from_candidates(candidates))
+    }
 }
 
 #[cfg(test)]
@@ -89,7 +121,7 @@ mod tests {
         let bot_w: Box<dyn Bot> = Box::new(RandomBot::new(Player::White));
         let bot_b: Box<dyn Bot> = Box::new(RandomBot::new(Player::Black));
 
-        let bots = vec![bot_w, bot_b];
+        let bots = [bot_w, bot_b];
         let mut players: Vec<Player> = bots
             .iter()
             .map(|bot| bot.get_own_player().clone())
@@ -140,5 +172,15 @@ mod tests {
             };
             assert_eq!(move_request, expected);
         }
+        let analysis = bot_b.analyze(&state).unwrap();
+        assert_eq!(analysis.candidates.len(), state.get_legal_moves().len());
+        // ties are broken differently by the two, so only the top score has to agree
+        let top = analysis.candidates[0].score;
+        assert!(analysis.candidates.iter().all(|c| c.score <= top));
+        let chosen = bot_b.get_move(&state);
+        assert!(analysis
+            .candidates
+            .iter()
+            .any(|c| c.request == chosen && c.score == top));
     }
 }
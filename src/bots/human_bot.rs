@@ -1,5 +1,6 @@
 use crate::tilebag::TileBag;
 use std::{
+    cell::RefCell,
     rc::Rc,
     sync::{
         mpsc::{Receiver, Sender},
@@ -15,13 +16,22 @@ use crate::{
     tile::{Rotation, TileClickTarget},
 };
 
-use super::bot::{Bot, MoveRequest};
+use super::{
+    bot::{Bot, MoveHint, MoveRequest},
+    spectator_bot::SpectatorControl,
+};
 pub struct HumanBot {
     pub own_player: Player,
     receiver: Rc<Mutex<Receiver<InteractionMessage>>>,
     sender: Sender<RenderMessage>,
+    // suggestions shown over the board, the human still picks the move
+    hints: Option<HintSource>,
+    // pace of a watched game, kept up to date while the human is thinking
+    spectator: Option<Rc<RefCell<SpectatorControl>>>,
 }
 
+pub type HintSource = Box<dyn FnMut(&RefereeState) -> Vec<MoveHint>>;
+
 impl HumanBot {
     pub fn new(
         player: Player,
@@ -32,9 +42,31 @@ impl HumanBot {
             own_player: player,
             receiver,
             sender,
+            hints: None,
+            spectator: None,
+        }
+    }
+
+    pub fn with_hints(self, hints: HintSource) -> Self {
+        Self {
+            hints: Some(hints),
+            ..self
+        }
+    }
+
+    pub fn with_spectator(self, control: Rc<RefCell<SpectatorControl>>) -> Self {
+        Self {
+            spectator: Some(control),
+            ..self
         }
     }
 
+    fn log(&self, reason: &str) {
+        self.sender
+            .send(RenderMessage::Log(format!("{}: {reason}", self.own_player)))
+            .ok();
+    }
+
     pub fn validate_tile_placement(
         &self,
         coord: Coordinate,
@@ -80,17 +112,23 @@ impl Bot for HumanBot {
     fn get_move(&mut self, state: &RefereeState) -> MoveRequest {
         let mut is_placing_meeple = false;
         let mut tile_data: Option<(Coordinate, Rotation)> = None;
+        let hints = match &mut self.hints {
+            Some(source) => source(state),
+            None => vec![],
+        };
         loop {
+            let mut render_state = state.clone_into_mid_move(tile_data.clone(), is_placing_meeple);
+            render_state.hints = hints.clone();
             self.sender
-                .send(RenderMessage::RefereeSync(
-                    state.clone_into_mid_move(tile_data.clone(), is_placing_meeple),
-                ))
+                .send(RenderMessage::RefereeSync(render_state))
                 .unwrap();
             match self.receiver.lock().unwrap().recv().unwrap() {
-                InteractionMessage::NextFrame
-                | InteractionMessage::PreviousFrame
-                | InteractionMessage::FirstFrame
-                | InteractionMessage::LastFrame => {}
+                InteractionMessage::ShowFrame(_) => {}
+                InteractionMessage::Spectate(command) => {
+                    if let Some(control) = &self.spectator {
+                        control.borrow_mut().apply(command);
+                    }
+                }
                 InteractionMessage::Print(message) => {
                     println!("recv {}", message);
                 }
@@ -120,8 +158,8 @@ impl Bot for HumanBot {
                                     rotation: rotation.clone(),
                                     meeple: Some(message.location),
                                 };
-                            } else {
-                                println!("{:?}", attempt);
+                            } else if let Err(reason) = attempt {
+                                self.log(reason);
                             }
                         }
                     } else {
@@ -133,8 +171,8 @@ impl Bot for HumanBot {
                         if attempt.is_ok() {
                             tile_data = Some((message.coord, message.rotation.clone()));
                             is_placing_meeple = true;
-                        } else {
-                            println!("{:?}", attempt);
+                        } else if let Err(reason) = attempt {
+                            self.log(reason);
                         };
                     }
                 }
@@ -1,32 +1,161 @@
 use core::panic;
 use itertools::Itertools;
 use rayon::prelude::*;
-use std::todo;
+use std::{
+    cmp::{min, Ordering},
+    sync::{
+        atomic::{AtomicU32, Ordering as AtomicOrdering},
+        Mutex,
+    },
+};
 
 use rand::{rngs::StdRng, Rng, SeedableRng};
+use rustc_hash::{FxHashMap, FxHashSet};
 
 use crate::{
-    arena::Match,
-    board::BoardData,
+    net::{encoding::Encoder, network::Network, self_play::outcome_value},
     referee::{Player, RefereeState},
     tile::TileData,
-    tilebag::{self, TileBag},
+    tilebag::TileBag,
+};
+
+use super::{
+    bot::{Analysis, Bot, CandidateMove, MoveRequest},
+    evaluator::Evaluator,
+    transposition::TranspositionTable,
 };
 
-use super::bot::{Bot, MoveRequest};
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum RewardNormalization {
+    /// +1 for a win, -1 for a loss, 0 for a draw
+    WinLoss,
+    /// Raw difference between own and opponent score
+    ScoreMargin,
+    /// Win/loss plus a weighted score margin as a tiebreak
+    Mixed,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum FinalMoveSelection {
+    /// Child with the most visits
+    MaxVisits,
+    /// Child with the best average reward
+    MaxValue,
+    /// Child that is best by both, searching further until one exists
+    RobustMax,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Parallelism {
+    /// One tree, rollouts of each expansion run in parallel
+    Leaf,
+    /// Independent trees per thread, root statistics merged at the end
+    Root,
+    /// One shared tree searched by several threads at once, kept apart by virtual loss
+    Tree,
+}
+
+#[derive(Clone, Debug)]
+pub struct MCTSConfig {
+    /// MCTS exploration constant used in UCB
+    pub exploration: f64,
+    /// How MCTS turns a rollout result into a reward
+    pub reward: RewardNormalization,
+    /// Weight of the score margin for mixed rewards
+    pub margin_weight: f64,
+    /// How MCTS picks the move to play once search is done
+    pub final_move: FinalMoveSelection,
+    /// Blend all-moves-as-first (AMAF) statistics into selection
+    pub rave: bool,
+    /// Visit count at which RAVE and UCB values are weighted equally
+    pub rave_equivalence: f64,
+    /// How MCTS spreads search across threads
+    pub parallelism: Parallelism,
+    /// Trees (root) or searching threads (tree), capped at the rayon pool size, 0 for all
+    pub threads: usize,
+    /// Reward subtracted from paths still being simulated under tree parallelism
+    pub virtual_loss: f64,
+    /// Score leaves with the static evaluator instead of random playouts
+    pub leaf_eval: bool,
+    /// Weights of the static evaluator used for leaves
+    pub evaluator: Evaluator,
+    /// Merge nodes reached through different move orders, searching a DAG
+    pub transpositions: bool,
+    /// Positions the transposition table holds before replacing entries
+    pub table_size: usize,
+}
+
+impl Default for MCTSConfig {
+    fn default() -> Self {
+        Self {
+            exploration: 2.0,
+            reward: RewardNormalization::ScoreMargin,
+            margin_weight: 0.01,
+            final_move: FinalMoveSelection::MaxValue,
+            rave: false,
+            rave_equivalence: 1000.0,
+            parallelism: Parallelism::Leaf,
+            threads: 0,
+            virtual_loss: 1.0,
+            leaf_eval: false,
+            evaluator: Evaluator::default(),
+            transpositions: false,
+            table_size: 1 << 16,
+        }
+    }
+}
+
+impl MCTSConfig {
+    pub fn thread_count(&self) -> usize {
+        let pool = rayon::current_num_threads();
+        if self.threads == 0 {
+            pool
+        } else {
+            min(self.threads, pool)
+        }
+    }
+
+    pub fn normalize(&self, margin: f64) -> f64 {
+        let win_loss = match margin.partial_cmp(&0.0) {
+            Some(Ordering::Greater) => 1.0,
+            Some(Ordering::Less) => -1.0,
+            Some(Ordering::Equal) | None => 0.0,
+        };
+        match self.reward {
+            RewardNormalization::WinLoss => win_loss,
+            RewardNormalization::ScoreMargin => margin,
+            RewardNormalization::Mixed => win_loss + self.margin_weight * margin,
+        }
+    }
+}
+
+pub fn score_margin(scores: &FxHashMap<Player, u32>, own_player: &Player) -> f64 {
+    let mut margin: i32 = 0;
+    for (player, points) in scores {
+        if player == own_player {
+            margin += *points as i32;
+        } else {
+            margin -= *points as i32;
+        }
+    }
+    margin as f64
+}
 
 pub struct MCTSBot {
     pub own_player: Player,
-    rng: StdRng,
     depth: u32,
+    config: MCTSConfig,
 }
 
 impl MCTSBot {
     pub fn new(player: Player, depth: u32) -> Self {
+        Self::with_config(player, depth, MCTSConfig::default())
+    }
+    pub fn with_config(player: Player, depth: u32, config: MCTSConfig) -> Self {
         MCTSBot {
-            rng: StdRng::seed_from_u64(rand::random()),
             own_player: player,
             depth,
+            config,
         }
     }
 }
@@ -41,18 +170,57 @@ impl Bot for MCTSBot {
     }
 
     fn get_move(&mut self, state: &RefereeState) -> MoveRequest {
-        let mut arena = ArenaTree::new(state);
-        for _i in 0..self.depth {
-            //            println!("arena iter {_i}");
-            //            arena.debug_data();
-            arena.mcts_iter();
-            // if arena.rollouts > self.depth {
-            //     break;
-            // }
+        self.searched_arena(state).recommend()
+    }
+
+    fn analyze(&mut self, state: &RefereeState) -> Option<Analysis> {
+        Some(self.searched_arena(state).analysis())
+    }
+}
+
+impl MCTSBot {
+    // `depth` is the iteration budget of the whole search, split between the trees
+    // under root parallelism
+    fn searched_arena<'a>(&self, state: &'a RefereeState) -> ArenaTree<'a> {
+        if self.config.parallelism != Parallelism::Root {
+            let mut arena = ArenaTree::new(state, self.config.clone());
+            self.search(&mut arena, self.depth);
+            return arena;
         }
-        //        println!("{} used rollouts {}", self.get_name(), arena.rollouts);
 
-        arena.recommend()
+        let trees = (self.config.thread_count() as u32).min(self.depth);
+        let mut trees: Vec<ArenaTree> = (0..trees)
+            .into_par_iter()
+            .map(|i| {
+                let budget = self.depth / trees + u32::from(i < self.depth % trees);
+                let mut arena = ArenaTree::new(state, self.config.clone());
+                self.search(&mut arena, budget);
+                arena
+            })
+            .collect();
+        let mut merged = trees.pop().unwrap();
+        for tree in &trees {
+            merged.merge_root(tree);
+        }
+        //        println!("{} used rollouts {}", self.get_name(), merged.rollouts);
+        merged
+    }
+
+    fn search(&self, arena: &mut ArenaTree, budget: u32) {
+        arena.run(budget);
+        if self.config.final_move == FinalMoveSelection::RobustMax {
+            // keep searching (up to double the budget) until visits and value agree
+            let step = match self.config.parallelism {
+                Parallelism::Tree => self.config.thread_count() as u32,
+                _ => 1,
+            };
+            let mut extra = 0;
+            while extra < budget && arena.robust_max_child().is_none() {
+                let iterations = step.min(budget - extra);
+                arena.run(iterations);
+                extra += iterations;
+            }
+        }
     }
 }
 
@@ -65,24 +233,39 @@ struct NodeData {
     visited: u64,
     player: Player,
     reward: f64,
+    // for the spread of rewards in analysis, virtual loss leaves it alone
+    reward_sq: f64,
+    amaf_visited: u64,
+    amaf_reward: f64,
+    // policy network probability of this move, only set when searching with a network
+    prior: f64,
     entry: Edge,
 }
 
-struct ArenaTree<'a> {
+pub(crate) struct ArenaTree<'a> {
     pub rollouts: u32,
     arena: Vec<Node>,
     rng: StdRng,
     state: &'a RefereeState,
+    config: MCTSConfig,
+    net: Option<&'a Network>,
+    // positions after a placement to their node, only with `config.transpositions`
+    table: Option<TranspositionTable<usize>>,
 }
 
 struct Node {
-    idx: usize,
     data: NodeData,
+    // the node that created this one, states are rebuilt along this path
     parent: Option<usize>,
     children: Vec<usize>,
+    // children created by another node, with the move that reaches them from here
+    transposed: Vec<(usize, MoveRequest)>,
 }
 
+type PlayedMoves = Vec<(Player, MoveRequest)>;
+
 impl<'a> ArenaTree<'a> {
+    #[allow(dead_code)]
     pub fn debug_data(&self) {
         println!(
             "own player {} child ct {}",
@@ -91,26 +274,64 @@ impl<'a> ArenaTree<'a> {
         );
     }
 
-    pub fn new(state: &'a RefereeState) -> Self {
+    pub fn new(state: &'a RefereeState, config: MCTSConfig) -> Self {
+        let table = config
+            .transpositions
+            .then(|| TranspositionTable::new(config.table_size));
         Self {
             rollouts: 0,
             state,
+            config,
+            net: None,
+            table,
             rng: StdRng::seed_from_u64(rand::random()),
             arena: vec![Node {
-                idx: 0,
                 data: NodeData {
                     visited: 0,
                     player: state.get_player().clone(),
                     reward: 0.0,
+                    reward_sq: 0.0,
+                    amaf_visited: 0,
+                    amaf_reward: 0.0,
+                    prior: 1.0,
                     entry: Edge::Draw(vec![state.tilebag.peek().unwrap().clone()]),
                 },
                 parent: None,
                 children: vec![],
+                transposed: vec![],
             }],
         }
     }
-    pub fn recommend(&self) -> MoveRequest {
-        let max_visited = self.arena[0]
+
+    // priors and values come from the network instead of rollouts, selection uses PUCT
+    pub fn with_network(state: &'a RefereeState, config: MCTSConfig, net: &'a Network) -> Self {
+        Self {
+            net: Some(net),
+            ..Self::new(state, config)
+        }
+    }
+
+    pub fn root_visits(&self) -> Vec<(MoveRequest, u64)> {
+        self.arena[0]
+            .children
+            .iter()
+            .filter_map(|idx| match &self.arena[*idx].data.entry {
+                Edge::Placement(request) => Some((request.clone(), self.arena[*idx].data.visited)),
+                Edge::Draw(_) => None,
+            })
+            .collect()
+    }
+
+    fn max_visits_child(&self) -> usize {
+        *self.arena[0]
+            .children
+            .iter()
+            .max_by_key(|idx| self.arena[**idx].data.visited)
+            .unwrap()
+    }
+
+    fn max_value_child(&self) -> usize {
+        *self.arena[0]
             .children
             .iter()
             .max_by(|l, r| {
@@ -118,44 +339,236 @@ impl<'a> ArenaTree<'a> {
                 let rv = self.arena[*r.to_owned()].data.average_reward();
                 lv.partial_cmp(&rv).unwrap()
             })
-            .unwrap();
-        if let Edge::Placement(out) = &self.arena[*max_visited].data.entry {
+            .unwrap()
+    }
+
+    fn robust_max_child(&self) -> Option<usize> {
+        let by_visits = self.max_visits_child();
+        let by_value = self.max_value_child();
+        if self.arena[by_value].data.visited >= self.arena[by_visits].data.visited {
+            Some(by_value)
+        } else {
+            None
+        }
+    }
+
+    fn best_child(&self) -> usize {
+        match self.config.final_move {
+            FinalMoveSelection::MaxVisits => self.max_visits_child(),
+            FinalMoveSelection::MaxValue => self.max_value_child(),
+            FinalMoveSelection::RobustMax => self
+                .robust_max_child()
+                .unwrap_or_else(|| self.max_visits_child()),
+        }
+    }
+
+    pub fn recommend(&self) -> MoveRequest {
+        if let Edge::Placement(out) = &self.arena[self.best_child()].data.entry {
             out.clone()
         } else {
             panic!()
         }
     }
 
+    // root moves with the recommended one first, then by visits. The principal
+    // variation follows the most visited tile draw and placement below it
+    pub fn analysis(&self) -> Analysis {
+        let best = self.best_child();
+        let margins = self.net.is_none() && self.config.reward == RewardNormalization::ScoreMargin;
+        let candidates = self.arena[0]
+            .children
+            .iter()
+            .sorted_by_key(|idx| {
+                (
+                    **idx != best,
+                    std::cmp::Reverse(self.arena[**idx].data.visited),
+                )
+            })
+            .filter_map(|idx| {
+                let data = &self.arena[*idx].data;
+                let request = self.edge_move(0, *idx)?.clone();
+                let score = data.average_reward();
+                Some(CandidateMove {
+                    request,
+                    score,
+                    visits: Some(data.visited),
+                    expected_margin: margins.then_some(score),
+                    std_error: data.std_error(),
+                })
+            })
+            .collect();
+
+        let mut principal_variation = vec![];
+        let (mut parent_idx, mut cur_idx) = (0, best);
+        loop {
+            principal_variation.extend(self.edge_move(parent_idx, cur_idx).cloned());
+            let Some(draw_idx) = self.most_visited_child(cur_idx) else {
+                break;
+            };
+            let Some(next_idx) = self.most_visited_child(draw_idx) else {
+                break;
+            };
+            if self.arena[next_idx].data.visited == 0 {
+                break;
+            }
+            (parent_idx, cur_idx) = (draw_idx, next_idx);
+        }
+        Analysis {
+            candidates,
+            principal_variation,
+        }
+    }
+
+    fn most_visited_child(&self, idx: usize) -> Option<usize> {
+        self.arena[idx]
+            .children
+            .iter()
+            .copied()
+            .max_by_key(|child| self.arena[*child].data.visited)
+    }
+
+    // sums root move statistics of another search over the same state into this one
+    fn merge_root(&mut self, other: &ArenaTree) {
+        let other_children: FxHashMap<&MoveRequest, &NodeData> = other.arena[0]
+            .children
+            .iter()
+            .filter_map(|idx| {
+                let data = &other.arena[*idx].data;
+                match &data.entry {
+                    Edge::Placement(request) => Some((request, data)),
+                    Edge::Draw(_) => None,
+                }
+            })
+            .collect();
+        for idx in self.arena[0].children.clone() {
+            let data = &mut self.arena[idx].data;
+            let other_data = match &data.entry {
+                Edge::Placement(request) => other_children.get(request),
+                Edge::Draw(_) => None,
+            };
+            if let Some(other_data) = other_data {
+                data.visited += other_data.visited;
+                data.reward += other_data.reward;
+                data.reward_sq += other_data.reward_sq;
+                data.amaf_visited += other_data.amaf_visited;
+                data.amaf_reward += other_data.amaf_reward;
+            }
+        }
+        self.arena[0].data.visited += other.arena[0].data.visited;
+        self.rollouts += other.rollouts;
+    }
+
+    fn add_virtual_loss(&mut self, path: &[usize], sign: f64) {
+        let loss = sign * self.config.virtual_loss;
+        for idx in path {
+            let data = &mut self.arena[*idx].data;
+            data.reward -= loss;
+            if sign > 0.0 {
+                data.visited += 1;
+            } else {
+                data.visited -= 1;
+            }
+        }
+    }
+
+    // `iterations` selections, by several threads sharing the tree under tree
+    // parallelism
+    pub fn run(&mut self, iterations: u32) {
+        if self.config.parallelism == Parallelism::Tree {
+            let threads = self.config.thread_count();
+            self.run_shared(iterations, threads);
+        } else {
+            for _ in 0..iterations {
+                self.mcts_iter();
+            }
+        }
+    }
+
     pub fn mcts_iter(&mut self) {
-        let expansion_idx = self.selection();
-        // let mut path = self.path_to_root(expansion_idx);
-        // path.reverse();
-        // let mut depth = 0;
-        // for elem in path {
-        //     let node = &self.arena[elem];
-        //     let entry = match node.data.entry {
-        //         Edge::Draw(_) => "Drew",
-        //         Edge::Placement(_) => "Placed",
-        //     };
-        //     println!(
-        //         "path to expansion {depth}, reward {} visited {}, player {}, action {entry}",
-        //         node.data.reward, node.data.visited, node.data.player,
-        //     );
-        //     depth += 1;
-        // }
-
-        let new_nodes = self.expansion(expansion_idx);
-        let results: Vec<(usize, f64)> = new_nodes
+        let (_, leaves) = self.select_and_expand();
+        let results: Vec<(Vec<usize>, f64, PlayedMoves)> = leaves
             .into_par_iter()
-            .map(|idx| (idx, self.simulation(idx)))
+            .map(|(idx, path)| {
+                let (reward, moves) = self.simulation(idx);
+                (path, reward, moves)
+            })
             .collect();
-
         self.rollouts += results.len() as u32;
-        for result in results {
-            self.back_prop(result.0, result.1);
+        for (path, reward, moves) in results {
+            self.back_prop(&path, reward, moves);
         }
     }
 
+    // the selected path and the nodes to simulate with their paths, the new children
+    // or the selected node itself if the game is over there
+    fn select_and_expand(&mut self) -> (Vec<usize>, Vec<(usize, Vec<usize>)>) {
+        let path = self.selection();
+        let expansion_idx = *path.last().unwrap();
+        let expanded = self.expansion(expansion_idx);
+        if expanded.is_empty() {
+            // game is over at this leaf (or every child was transposed), score it as is
+            return (path.clone(), vec![(expansion_idx, path)]);
+        }
+        let leaves = expanded
+            .into_iter()
+            .map(|idx| {
+                let mut child_path = path.clone();
+                child_path.push(idx);
+                (idx, child_path)
+            })
+            .collect();
+        (path, leaves)
+    }
+
+    // threads take turns selecting and expanding under a lock, with virtual loss on
+    // what they are still simulating, and run their rollouts outside of it
+    fn run_shared(&mut self, iterations: u32, threads: usize) {
+        let remaining = AtomicU32::new(iterations);
+        let own_player = self.arena[0].data.player.clone();
+        let (config, net) = (self.config.clone(), self.net);
+        let tree = Mutex::new(self);
+        (0..threads).into_par_iter().for_each(|_| {
+            while remaining
+                .fetch_update(AtomicOrdering::Relaxed, AtomicOrdering::Relaxed, |n| {
+                    n.checked_sub(1)
+                })
+                .is_ok()
+            {
+                let (path, pending, leaves) = {
+                    let mut tree = tree.lock().unwrap();
+                    let (path, leaves) = tree.select_and_expand();
+                    let pending: Vec<usize> = leaves
+                        .iter()
+                        .map(|(idx, _)| *idx)
+                        .filter(|idx| !path.contains(idx))
+                        .collect();
+                    tree.add_virtual_loss(&path, 1.0);
+                    tree.add_virtual_loss(&pending, 1.0);
+                    let leaves: Vec<(Vec<usize>, RefereeState)> = leaves
+                        .into_iter()
+                        .map(|(idx, path)| (path, tree.state_at(idx)))
+                        .collect();
+                    (path, pending, leaves)
+                };
+                let results: Vec<(Vec<usize>, f64, PlayedMoves)> = leaves
+                    .into_par_iter()
+                    .map(|(path, state)| {
+                        let (reward, moves) = Self::rollout(state, &own_player, &config, net);
+                        (path, reward, moves)
+                    })
+                    .collect();
+
+                let mut tree = tree.lock().unwrap();
+                tree.add_virtual_loss(&path, -1.0);
+                tree.add_virtual_loss(&pending, -1.0);
+                tree.rollouts += results.len() as u32;
+                for (path, reward, moves) in results {
+                    tree.back_prop(&path, reward, moves);
+                }
+            }
+        });
+    }
+
     fn state_at(&self, node_idx: usize) -> RefereeState {
         let mut path = self.path_to_root(node_idx);
         path.reverse();
@@ -167,7 +580,7 @@ impl<'a> ArenaTree<'a> {
                 Edge::Placement(request) => out.process_move(request.clone()).unwrap(),
             }
         }
-        return out;
+        out
     }
     fn path_to_root(&self, node_idx: usize) -> Vec<usize> {
         let mut out = vec![node_idx];
@@ -176,14 +589,52 @@ impl<'a> ArenaTree<'a> {
             cur_idx = next_idx;
             out.push(cur_idx);
         }
-        return out;
+        out
     }
 
-    fn back_prop(&mut self, start_idx: usize, reward: f64) {
-        let path = self.path_to_root(start_idx);
+    // the move leading from `parent_idx` to its child `child_idx`
+    fn edge_move(&self, parent_idx: usize, child_idx: usize) -> Option<&MoveRequest> {
+        if self.arena[child_idx].parent == Some(parent_idx) {
+            match &self.arena[child_idx].data.entry {
+                Edge::Placement(request) => Some(request),
+                Edge::Draw(_) => None,
+            }
+        } else {
+            self.arena[parent_idx]
+                .transposed
+                .iter()
+                .find(|(idx, _)| *idx == child_idx)
+                .map(|(_, request)| request)
+        }
+    }
 
+    // `path` runs from the root to the simulated node, as taken by selection
+    fn back_prop(&mut self, path: &[usize], reward: f64, rollout_moves: PlayedMoves) {
         let root_player = self.arena[0].data.player.clone();
-        for idx in path {
+        // moves played after the node currently being updated, for AMAF
+        let mut played: FxHashSet<(Player, MoveRequest)> = if self.config.rave {
+            rollout_moves.into_iter().collect()
+        } else {
+            FxHashSet::default()
+        };
+        for (depth, idx) in path.iter().copied().enumerate().rev() {
+            if self.config.rave {
+                for child_idx in self.arena[idx].children.clone() {
+                    if let Some(request) = self.edge_move(idx, child_idx).cloned() {
+                        let child = &mut self.arena[child_idx].data;
+                        if !played.contains(&(child.player.clone(), request)) {
+                            continue;
+                        }
+                        if child.player != root_player {
+                            child.amaf_reward -= reward;
+                        } else {
+                            child.amaf_reward += reward;
+                        }
+                        child.amaf_visited += 1;
+                    }
+                }
+            }
+
             let node = &mut self.arena[idx];
             if node.data.player != root_player {
                 node.data.reward -= reward;
@@ -191,29 +642,35 @@ impl<'a> ArenaTree<'a> {
                 node.data.reward += reward;
             }
             node.data.visited += 1;
+            node.data.reward_sq += reward * reward;
+            let player = node.data.player.clone();
+            if self.config.rave && depth > 0 {
+                if let Some(request) = self.edge_move(path[depth - 1], idx) {
+                    played.insert((player, request.clone()));
+                }
+            }
         }
     }
 
     fn insert_children(&mut self, children: Vec<NodeData>, parent_idx: usize) -> Vec<usize> {
         self.arena.reserve(children.len());
 
-        let mut prev_len = self.arena.len();
+        let prev_len = self.arena.len();
 
         let mut out = vec![];
-        for child in children {
+        for (i, child) in children.into_iter().enumerate() {
             self.arena.push(Node {
-                idx: prev_len,
                 data: child,
                 parent: Some(parent_idx),
                 children: vec![],
+                transposed: vec![],
             });
-            out.push(prev_len);
-            prev_len += 1;
+            out.push(prev_len + i);
         }
 
         let parent = &mut self.arena[parent_idx];
         parent.children.append(&mut out.clone());
-        return out;
+        out
     }
 
     fn expansion(&mut self, idx: usize) -> Vec<usize> {
@@ -222,10 +679,7 @@ impl<'a> ArenaTree<'a> {
         let edges: Vec<Edge> = match cur.data.entry {
             Edge::Draw(_) => {
                 let moves = state.get_legal_moves();
-                moves
-                    .into_iter()
-                    .map(|request| Edge::Placement(request))
-                    .collect()
+                moves.into_iter().map(Edge::Placement).collect()
             }
             Edge::Placement(_) => {
                 let remaining_tiles = state.tilebag.get_data().clone();
@@ -257,41 +711,125 @@ impl<'a> ArenaTree<'a> {
         };
 
         let player = state.get_player();
+        let priors = match (self.net, &cur.data.entry) {
+            (Some(net), Edge::Draw(_)) => Self::priors(net, &state, &edges),
+            _ => vec![1.0; edges.len()],
+        };
 
-        let data: Vec<NodeData> = edges
-            .into_iter()
-            .map(|edge| NodeData {
+        let mut data: Vec<NodeData> = vec![];
+        let mut keys = vec![];
+        for (edge, prior) in edges.into_iter().zip(priors) {
+            if let (Some(table), Edge::Placement(request)) = (&self.table, &edge) {
+                let mut next = state.clone();
+                next.process_move(request.clone()).unwrap();
+                let key = next.position_hash();
+                if keys.contains(&key) {
+                    // a sibling already leads here
+                    continue;
+                }
+                if let Some(&existing) = table.get(key) {
+                    let parent = &mut self.arena[idx];
+                    if !parent.children.contains(&existing) {
+                        parent.children.push(existing);
+                        parent.transposed.push((existing, request.clone()));
+                    }
+                    continue;
+                }
+                keys.push(key);
+            }
+            data.push(NodeData {
                 player: player.clone(),
                 entry: edge,
                 reward: 0.0,
+                reward_sq: 0.0,
                 visited: 0,
+                amaf_visited: 0,
+                amaf_reward: 0.0,
+                prior,
+            });
+        }
+        let created = self.insert_children(data, idx);
+        if let Some(table) = &mut self.table {
+            // newest node wins a slot, older ones just stop being found
+            for (key, node_idx) in keys.into_iter().zip(&created) {
+                table.insert(key, 0, *node_idx);
+            }
+        }
+        created
+    }
+
+    // softmax of the policy logits over the legal moves only
+    fn priors(net: &Network, state: &RefereeState, edges: &[Edge]) -> Vec<f64> {
+        let encoder = Encoder::new(&state.board);
+        let logits = net.policy(&encoder.encode(state));
+        let picked: Vec<f64> = edges
+            .iter()
+            .map(|edge| match edge {
+                Edge::Placement(request) => encoder
+                    .move_index(request)
+                    .map(|i| logits[i] as f64)
+                    .unwrap_or(0.0),
+                Edge::Draw(_) => 0.0,
             })
             .collect();
-        self.insert_children(data, idx)
+        let max = picked.iter().cloned().fold(f64::MIN, f64::max);
+        let exps: Vec<f64> = picked.iter().map(|logit| (logit - max).exp()).collect();
+        let total: f64 = exps.iter().sum();
+        exps.into_iter().map(|e| e / total).collect()
     }
 
-    fn simulation(&self, idx: usize) -> f64 {
-        let mut out: f64 = 0.0;
-        let state = self.state_at(idx);
-        let own_player = self.arena[0].data.player.clone();
-        let result = Match::play_random_from_state(state).unwrap();
-        for (player, points) in result.player_scores {
-            if player == own_player {
-                out += points as f64;
+    fn simulation(&self, idx: usize) -> (f64, PlayedMoves) {
+        let own_player = &self.arena[0].data.player;
+        Self::rollout(self.state_at(idx), own_player, &self.config, self.net)
+    }
+
+    // reward of `state` for `own_player`, with the moves played to get it for RAVE
+    fn rollout(
+        mut state: RefereeState,
+        own_player: &Player,
+        config: &MCTSConfig,
+        net: Option<&Network>,
+    ) -> (f64, PlayedMoves) {
+        if let Some(net) = net {
+            if !state.tilebag.ensure_legal_draw(&state.board.as_overlay()) {
+                let margin = score_margin(&state.get_final_scores(), own_player);
+                return (outcome_value(margin) as f64, vec![]);
+            }
+            // the value head scores the position for whoever is to move
+            let value = net.value(&Encoder::new(&state.board).encode(&state)) as f64;
+            let reward = if &state.get_player() == own_player {
+                value
             } else {
-                out -= points as f64;
+                -value
+            };
+            return (reward, vec![]);
+        }
+        if config.leaf_eval {
+            let margin = config.evaluator.evaluate(&state, own_player);
+            return (config.normalize(margin), vec![]);
+        }
+        let mut rng = rand::thread_rng();
+        let mut played = vec![];
+        while state.tilebag.ensure_legal_draw(&state.board.as_overlay()) {
+            let moves = state.get_legal_moves();
+            let request = moves[rng.gen_range(0..moves.len())].clone();
+            if config.rave {
+                played.push((state.get_player(), request.clone()));
             }
+            state.process_move(request).unwrap();
         }
-        out
+        let margin = score_margin(&state.get_final_scores(), own_player);
+        let reward = config.normalize(margin);
+        (reward, played)
     }
 
-    fn selection(&mut self) -> usize {
+    fn selection(&mut self) -> Vec<usize> {
         let mut cur_idx = 0;
-        let mut parent_visited = 0;
+        let mut path = vec![0];
         loop {
             let cur = &self.arena[cur_idx];
             let next_idx = match cur.data.entry {
-                Edge::Draw(_) => self.max_ucb_idx(cur_idx, parent_visited),
+                Edge::Draw(_) => self.max_ucb_idx(cur_idx),
                 Edge::Placement(_) => {
                     let n = cur.children.len();
                     if n == 0 {
@@ -302,20 +840,29 @@ impl<'a> ArenaTree<'a> {
                     }
                 }
             };
-            parent_visited = self.arena[cur_idx].data.visited;
             if let Some(next_idx) = next_idx {
                 cur_idx = next_idx;
+                path.push(cur_idx);
             } else {
-                return cur_idx;
+                return path;
             }
         }
     }
 
-    fn max_ucb_idx(&self, start_idx: usize, parent_visited: u64) -> Option<usize> {
+    fn max_ucb_idx(&self, start_idx: usize) -> Option<usize> {
+        let parent_visited = self.arena[start_idx].data.visited;
         self.arena[start_idx]
             .children
             .iter()
-            .map(|c| (c, self.arena[*c].ucb(parent_visited)))
+            .map(|c| {
+                let node = &self.arena[*c];
+                let score = if self.net.is_some() {
+                    node.puct(parent_visited, &self.config)
+                } else {
+                    node.ucb(parent_visited, &self.config)
+                };
+                (c, score)
+            })
             .max_by(|l, r| l.1.partial_cmp(&r.1).unwrap())
             .map(|x| x.0)
             .copied()
@@ -323,10 +870,13 @@ impl<'a> ArenaTree<'a> {
 }
 
 impl Node {
-    fn ucb(&self, parent_visited: u64) -> f64 {
-        let c = 2.0;
-
-        let ev = self.data.average_reward();
+    fn ucb(&self, parent_visited: u64, config: &MCTSConfig) -> f64 {
+        let mut ev = self.data.average_reward();
+        if config.rave && self.data.amaf_visited > 0 {
+            let k = config.rave_equivalence;
+            let beta = (k / (3.0 * self.data.visited as f64 + k)).sqrt();
+            ev = (1.0 - beta) * ev + beta * self.data.average_amaf_reward();
+        }
         if parent_visited == 0 {
             return ev;
         }
@@ -334,15 +884,203 @@ impl Node {
         let own_visits = self.data.visited as f64;
         let parent_visits = parent_visited as f64;
         let explore = (parent_visits.ln() / own_visits).sqrt();
-        return ev + c * explore;
+        ev + config.exploration * explore
+    }
+
+    fn puct(&self, parent_visited: u64, config: &MCTSConfig) -> f64 {
+        let explore = (parent_visited as f64).sqrt() / (1.0 + self.data.visited as f64);
+        self.data.average_reward() + config.exploration * self.data.prior * explore
     }
 }
 impl NodeData {
     pub fn average_reward(&self) -> f64 {
         if self.visited != 0 {
-            (self.reward as f64) / (self.visited as f64)
+            self.reward / (self.visited as f64)
         } else {
             0.0
         }
     }
+    pub fn std_error(&self) -> Option<f64> {
+        if self.visited < 2 {
+            return None;
+        }
+        let n = self.visited as f64;
+        let mean = self.average_reward();
+        let variance = (self.reward_sq / n - mean * mean).max(0.0);
+        Some((variance / n).sqrt())
+    }
+    pub fn average_amaf_reward(&self) -> f64 {
+        if self.amaf_visited != 0 {
+            self.amaf_reward / (self.amaf_visited as f64)
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tile::{MiniTile, TileDataBuilder},
+        tilebag::ReplayTileBag,
+    };
+
+    fn small_game() -> RefereeState {
+        let first: TileData = TileDataBuilder {
+            top: MiniTile::City,
+            left: MiniTile::Road,
+            center: MiniTile::Road,
+            right: MiniTile::Road,
+            ..Default::default()
+        }
+        .into();
+        let second: TileData = TileDataBuilder {
+            right: MiniTile::Road,
+            ..Default::default()
+        }
+        .into();
+        let third: TileData = TileDataBuilder {
+            left: MiniTile::Road,
+            center: MiniTile::Junction,
+            ..Default::default()
+        }
+        .into();
+        let bag = ReplayTileBag::new(vec![first, second, third]);
+        let mut state = RefereeState::from_players(vec![Player::White, Player::Black], bag.into());
+        state
+            .process_move(MoveRequest {
+                coord: (0, 0),
+                ..Default::default()
+            })
+            .unwrap();
+        state
+    }
+
+    #[test]
+    fn normalize_rewards() {
+        let mut scores = FxHashMap::default();
+        scores.insert(Player::White, 10);
+        scores.insert(Player::Black, 4);
+        let mut config = MCTSConfig::default();
+        let white = score_margin(&scores, &Player::White);
+        let black = score_margin(&scores, &Player::Black);
+        assert_eq!(config.normalize(white), 6.0);
+        assert_eq!(config.normalize(black), -6.0);
+        config.reward = RewardNormalization::WinLoss;
+        assert_eq!(config.normalize(black), -1.0);
+        assert_eq!(config.normalize(0.0), 0.0);
+        config.reward = RewardNormalization::Mixed;
+        assert!((config.normalize(10.0) - 1.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn every_config_recommends_legal_move() {
+        let state = small_game();
+        let legal = state.get_legal_moves();
+        for final_move in [
+            FinalMoveSelection::MaxVisits,
+            FinalMoveSelection::MaxValue,
+            FinalMoveSelection::RobustMax,
+        ] {
+            for (rave, leaf_eval, transpositions) in [
+                (false, false, false),
+                (true, false, false),
+                (false, true, false),
+                (true, false, true),
+            ] {
+                for parallelism in [Parallelism::Leaf, Parallelism::Root, Parallelism::Tree] {
+                    let config = MCTSConfig {
+                        final_move,
+                        rave,
+                        leaf_eval,
+                        transpositions,
+                        parallelism,
+                        threads: 2,
+                        ..Default::default()
+                    };
+                    let mut bot = MCTSBot::with_config(Player::Black, 20, config);
+                    assert!(legal.contains(&bot.get_move(&state)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn shared_tree_undoes_virtual_loss() {
+        let state = small_game();
+        let config = MCTSConfig {
+            parallelism: Parallelism::Tree,
+            ..Default::default()
+        };
+        let mut arena = ArenaTree::new(&state, config);
+        arena.run_shared(30, 4);
+        // every rollout went through the root once, and nothing else is left on it
+        assert_eq!(arena.arena[0].data.visited, arena.rollouts as u64);
+        let child_visits: u64 = arena.arena[0]
+            .children
+            .iter()
+            .map(|idx| arena.arena[*idx].data.visited)
+            .sum();
+        assert_eq!(child_visits, arena.rollouts as u64);
+    }
+
+    #[test]
+    fn transpositions_merge_move_orders() {
+        let mut state = small_game();
+        let straight: TileData = TileDataBuilder {
+            left: MiniTile::Road,
+            center: MiniTile::Road,
+            right: MiniTile::Road,
+            ..Default::default()
+        }
+        .into();
+        state.tilebag =
+            ReplayTileBag::new(vec![straight.clone(), straight.clone(), straight]).into();
+        let config = MCTSConfig {
+            transpositions: true,
+            leaf_eval: true,
+            ..Default::default()
+        };
+        let mut arena = ArenaTree::new(&state, config);
+        for _ in 0..2000 {
+            arena.mcts_iter();
+        }
+        let merged = arena
+            .arena
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, node)| node.transposed.iter().map(move |edge| (idx, edge)))
+            .next();
+        let (parent_idx, (child_idx, request)) = merged.expect("no transposition found");
+        // both paths rebuild the same position
+        let mut via_parent = arena.state_at(parent_idx);
+        via_parent.process_move(request.clone()).unwrap();
+        assert_eq!(
+            via_parent.position_hash(),
+            arena.state_at(*child_idx).position_hash()
+        );
+    }
+
+    #[test]
+    fn analysis_ranks_every_root_move() {
+        let state = small_game();
+        let config = MCTSConfig {
+            final_move: FinalMoveSelection::MaxVisits,
+            ..Default::default()
+        };
+        let mut bot = MCTSBot::with_config(Player::Black, 50, config);
+        let analysis = bot.analyze(&state).unwrap();
+
+        assert_eq!(analysis.candidates.len(), state.get_legal_moves().len());
+        let visits: Vec<u64> = analysis
+            .candidates
+            .iter()
+            .map(|candidate| candidate.visits.unwrap())
+            .collect();
+        assert!(visits.windows(2).all(|pair| pair[0] >= pair[1]));
+        assert_eq!(analysis.principal_variation.first(), analysis.best());
+        let hints = analysis.hints();
+        assert!((hints.iter().map(|hint| hint.weight).sum::<f32>() - 1.0).abs() < 1e-4);
+    }
 }
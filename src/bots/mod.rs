@@ -1,7 +1,16 @@
+pub mod book_bot;
 pub mod bot;
+pub mod eval_bot;
+pub mod evaluator;
+pub mod expectimax_bot;
+pub mod explain_bot;
 pub mod greedy_bot;
 pub mod human_bot;
 pub mod mcts_bot;
+pub mod net_bot;
 pub mod random_bot;
+pub mod registry;
 pub mod replay_bot;
 pub mod shallow_bot;
+pub mod spectator_bot;
+pub mod transposition;
@@ -0,0 +1,122 @@
+use std::sync::Arc;
+
+use rand::{distributions::WeightedIndex, prelude::Distribution};
+
+use crate::{
+    net::{
+        encoding::Encoder,
+        network::Network,
+        self_play::{PendingSample, SampleRecorder},
+    },
+    referee::{Player, RefereeState},
+};
+
+use super::{
+    bot::{Analysis, Bot, MoveRequest},
+    mcts_bot::{ArenaTree, FinalMoveSelection, MCTSConfig},
+};
+
+// MCTS guided by a policy/value network instead of random rollouts
+pub struct NetBot {
+    pub own_player: Player,
+    iterations: u32,
+    net: Arc<Network>,
+    pub config: MCTSConfig,
+    // during self-play, searched positions are kept here and moves are sampled by visits
+    recorder: Option<SampleRecorder>,
+}
+
+impl NetBot {
+    pub fn new(player: Player, iterations: u32, net: Arc<Network>) -> Self {
+        NetBot {
+            own_player: player,
+            iterations,
+            net,
+            config: MCTSConfig {
+                exploration: 1.5,
+                final_move: FinalMoveSelection::MaxVisits,
+                ..Default::default()
+            },
+            recorder: None,
+        }
+    }
+
+    pub fn recording(
+        player: Player,
+        iterations: u32,
+        net: Arc<Network>,
+        recorder: SampleRecorder,
+    ) -> Self {
+        NetBot {
+            recorder: Some(recorder),
+            ..Self::new(player, iterations, net)
+        }
+    }
+}
+
+impl Bot for NetBot {
+    fn get_name(&self) -> String {
+        format!("net bot {}", self.iterations)
+    }
+
+    fn get_own_player(&self) -> &Player {
+        &self.own_player
+    }
+
+    fn get_move(&mut self, state: &RefereeState) -> MoveRequest {
+        let mut arena = ArenaTree::with_network(state, self.config.clone(), &self.net);
+        for _ in 0..self.iterations {
+            arena.mcts_iter();
+        }
+        let Some(recorder) = &self.recorder else {
+            return arena.recommend();
+        };
+
+        let visits = arena.root_visits();
+        let encoder = Encoder::new(&state.board);
+        // moves outside the window have no policy output, the rest share the whole target
+        let kept: Vec<(usize, u64)> = visits
+            .iter()
+            .filter_map(|(request, n)| Some((encoder.move_index(request)?, *n)))
+            .collect();
+        let total: u64 = kept.iter().map(|(_, n)| n).sum();
+        let policy = kept
+            .into_iter()
+            .map(|(idx, n)| (idx, n as f32 / total.max(1) as f32))
+            .collect();
+        recorder.lock().unwrap().push(PendingSample {
+            input: encoder.encode(state),
+            policy,
+            player: self.own_player.clone(),
+        });
+
+        match WeightedIndex::new(visits.iter().map(|(_, n)| *n)) {
+            Ok(dist) => visits[dist.sample(&mut rand::thread_rng())].0.clone(),
+            Err(_) => arena.recommend(),
+        }
+    }
+
+    fn analyze(&mut self, state: &RefereeState) -> Option<Analysis> {
+        let mut arena = ArenaTree::with_network(state, self.config.clone(), &self.net);
+        for _ in 0..self.iterations {
+            arena.mcts_iter();
+        }
+        Some(arena.analysis())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::self_play;
+
+    #[test]
+    fn self_play_labels_every_position() {
+        let net = Arc::new(Network::new(4));
+        let samples = self_play::self_play_game(&net, 2);
+        assert!(!samples.is_empty());
+        assert!(samples
+            .iter()
+            .all(|sample| (-1.0..=1.0).contains(&sample.value) && !sample.policy.is_empty()));
+    }
+}
@@ -0,0 +1,327 @@
+use std::{fmt, path::PathBuf, str::FromStr, sync::Arc};
+
+use clap::ValueEnum;
+
+use crate::{arena::MessageResult, book::OpeningBook, net::network::Network, referee::Player};
+
+use super::{
+    book_bot::BookBot, bot::Bot, eval_bot::EvalBot, evaluator::Evaluator,
+    expectimax_bot::ExpectimaxBot, greedy_bot::GreedyBot, mcts_bot::MCTSBot, mcts_bot::MCTSConfig,
+    net_bot::NetBot, random_bot::RandomBot, shallow_bot::ShallowBot,
+};
+
+pub const SPEC_HELP: &str = "\
+bot spec, `kind` or `kind:key=value,...`. Kinds and keys:
+  human
+  random
+  greedy
+  shallow     depth
+  eval        meeple, features
+  expectimax  depth, tt
+  mcts        iters, c, reward, margin_weight, final, rave, rave_k,
+              parallelism, threads, virtual_loss, leaf_eval, meeple, features,
+              tt, tt_size
+  net         model (required), iters, c
+Any kind also takes `book=FILE` to play from an opening book while it has
+the position, a human player is shown the book's move frequencies instead";
+
+#[derive(Clone, Debug)]
+pub enum BotKind {
+    Human,
+    Random,
+    Greedy,
+    Shallow {
+        depth: u32,
+    },
+    Eval {
+        evaluator: Evaluator,
+    },
+    Expectimax {
+        depth: u32,
+        transpositions: bool,
+    },
+    MCTS {
+        iterations: u32,
+        config: MCTSConfig,
+    },
+    Net {
+        iterations: u32,
+        exploration: Option<f64>,
+        net: Arc<Network>,
+    },
+}
+
+// A bot described by a spec string such as `mcts:iters=500,c=1.4`
+#[derive(Clone, Debug)]
+pub struct BotSpec {
+    pub kind: BotKind,
+    pub book: Option<Arc<OpeningBook>>,
+    spec: String,
+}
+
+fn parse_value<T: FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value `{value}` for `{key}`"))
+}
+
+fn parse_enum<T: ValueEnum>(key: &str, value: &str) -> Result<T, String> {
+    T::from_str(value, true).map_err(|_| format!("invalid value `{value}` for `{key}`"))
+}
+
+impl FromStr for BotSpec {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let (name, params) = spec.split_once(':').unwrap_or((spec, ""));
+        let params: Vec<(&str, &str)> = params
+            .split(',')
+            .filter(|param| !param.is_empty())
+            .map(|param| {
+                param
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected key=value, got `{param}`"))
+            })
+            .collect::<Result<_, _>>()?;
+        let (book_params, params): (Vec<_>, Vec<_>) =
+            params.into_iter().partition(|(key, _)| *key == "book");
+        let book = match book_params.last() {
+            Some((_, path)) => Some(Arc::new(OpeningBook::from_path(PathBuf::from(path))?)),
+            None => None,
+        };
+        let unknown = |key: &str| Err(format!("unknown parameter `{key}` for `{name}` bot"));
+
+        let mut kind = match name {
+            "human" => BotKind::Human,
+            "random" => BotKind::Random,
+            "greedy" => BotKind::Greedy,
+            "shallow" => BotKind::Shallow { depth: 100 },
+            "eval" => BotKind::Eval {
+                evaluator: Evaluator::default(),
+            },
+            "expectimax" => BotKind::Expectimax {
+                depth: 2,
+                transpositions: true,
+            },
+            "mcts" => BotKind::MCTS {
+                iterations: 100,
+                config: MCTSConfig::default(),
+            },
+            "net" => {
+                let model = params
+                    .iter()
+                    .find(|(key, _)| *key == "model")
+                    .ok_or("net bot needs a `model` file")?;
+                let net = Network::from_path(PathBuf::from(model.1))?;
+                BotKind::Net {
+                    iterations: 100,
+                    exploration: None,
+                    net: Arc::new(net),
+                }
+            }
+            _ => return Err(format!("unknown bot `{name}`")),
+        };
+
+        for (key, value) in params {
+            match (&mut kind, key) {
+                (BotKind::Shallow { depth }, "depth")
+                | (BotKind::Expectimax { depth, .. }, "depth") => *depth = parse_value(key, value)?,
+                (BotKind::Expectimax { transpositions, .. }, "tt") => {
+                    *transpositions = parse_value(key, value)?
+                }
+                (BotKind::Eval { evaluator }, "meeple") => {
+                    evaluator.meeple_value = parse_value(key, value)?
+                }
+                (BotKind::Eval { evaluator }, "features") => {
+                    evaluator.feature_weight = parse_value(key, value)?
+                }
+                (BotKind::MCTS { iterations, .. }, "iters")
+                | (BotKind::Net { iterations, .. }, "iters") => {
+                    *iterations = parse_value(key, value)?;
+                    if *iterations == 0 {
+                        return Err(format!("`{key}` has to be at least 1"));
+                    }
+                }
+                (BotKind::Net { exploration, .. }, "c") => {
+                    *exploration = Some(parse_value(key, value)?)
+                }
+                (BotKind::Net { .. }, "model") => {}
+                (BotKind::MCTS { config, .. }, _) => match key {
+                    "c" => config.exploration = parse_value(key, value)?,
+                    "reward" => config.reward = parse_enum(key, value)?,
+                    "margin_weight" => config.margin_weight = parse_value(key, value)?,
+                    "final" => config.final_move = parse_enum(key, value)?,
+                    "rave" => config.rave = parse_value(key, value)?,
+                    "rave_k" => config.rave_equivalence = parse_value(key, value)?,
+                    "parallelism" => config.parallelism = parse_enum(key, value)?,
+                    "threads" => config.threads = parse_value(key, value)?,
+                    "virtual_loss" => config.virtual_loss = parse_value(key, value)?,
+                    "leaf_eval" => config.leaf_eval = parse_value(key, value)?,
+                    "meeple" => config.evaluator.meeple_value = parse_value(key, value)?,
+                    "features" => config.evaluator.feature_weight = parse_value(key, value)?,
+                    "tt" => config.transpositions = parse_value(key, value)?,
+                    "tt_size" => config.table_size = parse_value(key, value)?,
+                    _ => return unknown(key),
+                },
+                _ => return unknown(key),
+            }
+        }
+        Ok(Self {
+            kind,
+            book,
+            spec: spec.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for BotSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.spec)
+    }
+}
+
+impl BotSpec {
+    pub fn is_human(&self) -> bool {
+        matches!(self.kind, BotKind::Human)
+    }
+
+    // for games played without a window, where no human can take a seat
+    pub fn require_headless(&self) -> MessageResult<()> {
+        match self.is_human() {
+            true => Err("human players can only join through `play`"),
+            false => Ok(()),
+        }
+    }
+
+    // sets the search iterations of sampling bots (rollouts per move for `shallow`),
+    // others keep the effort given by their spec
+    pub fn with_budget(mut self, budget: u32) -> MessageResult<Self> {
+        if budget == 0 {
+            return Err("budget must be at least 1");
+        }
+        match &mut self.kind {
+            BotKind::Shallow { depth } => *depth = budget,
+            BotKind::MCTS { iterations, .. } | BotKind::Net { iterations, .. } => {
+                *iterations = budget
+            }
+            _ => {}
+        }
+        Ok(self)
+    }
+
+    // human bots need the GUI channels, so callers construct those themselves
+    pub fn build(&self, player: Player) -> MessageResult<Box<dyn Bot>> {
+        let bot: Box<dyn Bot> = match &self.kind {
+            BotKind::Human => return Err("human bots cannot be built from a spec alone"),
+            BotKind::Random => Box::new(RandomBot::new(player)),
+            BotKind::Greedy => Box::new(GreedyBot::new(player)),
+            BotKind::Shallow { depth } => Box::new(ShallowBot::new(player, *depth)),
+            BotKind::Eval { evaluator } => {
+                Box::new(EvalBot::with_evaluator(player, evaluator.clone()))
+            }
+            BotKind::Expectimax {
+                depth,
+                transpositions,
+            } => {
+                let mut bot = ExpectimaxBot::new(player, *depth);
+                bot.transpositions = *transpositions;
+                Box::new(bot)
+            }
+            BotKind::MCTS { iterations, config } => {
+                Box::new(MCTSBot::with_config(player, *iterations, config.clone()))
+            }
+            BotKind::Net {
+                iterations,
+                exploration,
+                net,
+            } => {
+                let mut bot = NetBot::new(player, *iterations, net.clone());
+                if let Some(c) = exploration {
+                    bot.config.exploration = *c;
+                }
+                Box::new(bot)
+            }
+        };
+        Ok(match &self.book {
+            Some(book) => Box::new(BookBot::new(bot, book.clone())),
+            None => bot,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bots::mcts_bot::RewardNormalization;
+    use std::fs;
+
+    #[test]
+    fn parses_params() {
+        let spec: BotSpec =
+            "mcts:iters=500,c=1.4,reward=win-loss,tt=true,leaf_eval=true,meeple=2.5"
+                .parse()
+                .unwrap();
+        match &spec.kind {
+            BotKind::MCTS { iterations, config } => {
+                assert_eq!(*iterations, 500);
+                assert_eq!(config.exploration, 1.4);
+                assert_eq!(config.reward, RewardNormalization::WinLoss);
+                assert!(config.transpositions);
+                assert!(config.leaf_eval);
+                assert_eq!(config.evaluator.meeple_value, 2.5);
+            }
+            _ => panic!("wrong kind"),
+        }
+        assert_eq!(
+            spec.to_string(),
+            "mcts:iters=500,c=1.4,reward=win-loss,tt=true,leaf_eval=true,meeple=2.5"
+        );
+
+        assert!(spec.clone().with_budget(0).is_err());
+        assert!(matches!(
+            spec.with_budget(20).unwrap().kind,
+            BotKind::MCTS { iterations: 20, .. }
+        ));
+
+        let bot = "shallow:depth=50".parse::<BotSpec>().unwrap();
+        assert_eq!(
+            bot.build(Player::White).unwrap().get_name(),
+            ShallowBot::new(Player::White, 50).get_name()
+        );
+    }
+
+    #[test]
+    fn wraps_bots_with_book() {
+        let dir =
+            std::env::temp_dir().join(format!("crabcassonne_registry_{}", rand::random::<u32>()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("opening.book");
+        OpeningBook::new(4).save(path.clone()).unwrap();
+
+        let spec: BotSpec = format!("shallow:book={},depth=3", path.display())
+            .parse()
+            .unwrap();
+        assert!(matches!(spec.kind, BotKind::Shallow { depth: 3 }));
+        assert_eq!(
+            spec.build(Player::White).unwrap().get_name(),
+            format!("{} (book)", ShallowBot::new(Player::White, 3).get_name())
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_bad_specs() {
+        assert!("minimax".parse::<BotSpec>().is_err());
+        assert!("greedy:depth=3".parse::<BotSpec>().is_err());
+        assert!("shallow:depth=deep".parse::<BotSpec>().is_err());
+        assert!("mcts:iters".parse::<BotSpec>().is_err());
+        assert!("mcts:iters=0".parse::<BotSpec>().is_err());
+        assert!("net:iters=5".parse::<BotSpec>().is_err());
+        assert!("greedy:book=missing.book".parse::<BotSpec>().is_err());
+        assert!("human"
+            .parse::<BotSpec>()
+            .unwrap()
+            .build(Player::Black)
+            .is_err());
+    }
+}
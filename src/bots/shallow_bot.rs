@@ -1,4 +1,3 @@
-use crate::tilebag::TileBag;
 use rayon::prelude::*;
 use std::cmp::Ordering;
 
@@ -10,7 +9,7 @@ use crate::{
     referee::{Player, RefereeState},
 };
 
-use super::bot::{Bot, MoveRequest};
+use super::bot::{Analysis, Bot, CandidateMove, MoveRequest};
 
 pub struct ShallowBot {
     pub own_player: Player,
@@ -28,6 +27,38 @@ impl ShallowBot {
     }
 }
 
+impl ShallowBot {
+    // summed score margin (and squared margin) of `depth` random playouts after each
+    // legal move
+    fn rollout_totals(&self, state: &RefereeState) -> Vec<(MoveRequest, i32, i64)> {
+        let own_player = self.get_own_player().clone();
+        state
+            .get_legal_moves()
+            .into_iter()
+            .map(|move_request| {
+                let (total, total_sq) = (0..self.depth)
+                    .into_par_iter()
+                    .map(|_i| {
+                        let mut out: i32 = 0;
+                        let mut state = state.clone();
+                        state.process_move(move_request.clone()).unwrap();
+                        let result = Match::play_random_from_state(state).unwrap();
+                        for (player, points) in result.player_scores {
+                            if player == own_player {
+                                out += points as i32;
+                            } else {
+                                out -= points as i32;
+                            }
+                        }
+                        (out, out as i64 * out as i64)
+                    })
+                    .reduce(|| (0, 0), |a, b| (a.0 + b.0, a.1 + b.1));
+                (move_request, total, total_sq)
+            })
+            .collect()
+    }
+}
+
 impl Bot for ShallowBot {
     fn get_name(&self) -> String {
         format!("shallow bot {}", self.depth)
@@ -38,9 +69,6 @@ impl Bot for ShallowBot {
     }
 
     fn get_move(&mut self, state: &RefereeState) -> MoveRequest {
-        let own_player = self.get_own_player().clone();
-
-        let moves: Vec<MoveRequest> = state.get_legal_moves();
         let mut candidate: Option<(MoveRequest, i32)> = None;
 
         // println!(
@@ -49,25 +77,7 @@ impl Bot for ShallowBot {
         //     (moves.len() as u32) * self.depth
         // );
 
-        for move_request in moves {
-            let total = (0..self.depth)
-                .into_par_iter()
-                .map(|_i| {
-                    let mut out: i32 = 0;
-                    let mut state = state.clone();
-                    state.process_move(move_request.clone()).unwrap();
-                    let result = Match::play_random_from_state(state).unwrap();
-                    for (player, points) in result.player_scores {
-                        if player == own_player {
-                            out += points as i32;
-                        } else {
-                            out -= points as i32;
-                        }
-                    }
-                    out
-                })
-                .sum();
-
+        for (move_request, total, _) in self.rollout_totals(state) {
             if let Some((_request, score)) = candidate.clone() {
                 match score.cmp(&total) {
                     Ordering::Less => {
@@ -86,4 +96,24 @@ impl Bot for ShallowBot {
         }
         candidate.unwrap().0
     }
+
+    fn analyze(&mut self, state: &RefereeState) -> Option<Analysis> {
+        let candidates: Vec<CandidateMove> = self
+            .rollout_totals(state)
+            .into_iter()
+            .map(|(request, total, total_sq)| {
+                let n = self.depth.max(1) as f64;
+                let margin = total as f64 / n;
+                let variance = (total_sq as f64 / n - margin * margin).max(0.0);
+                CandidateMove {
+                    request,
+                    score: margin,
+                    visits: None,
+                    expected_margin: Some(margin),
+                    std_error: Some((variance / n).sqrt()),
+                }
+            })
+            .collect();
+        Some(Analysis::from_candidates(candidates))
+    }
 }
@@ -0,0 +1,150 @@
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    sync::{
+        mpsc::{Receiver, RecvTimeoutError, Sender},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use rustc_hash::FxHashSet;
+
+use crate::{
+    referee::{Player, RefereeState},
+    render::{InteractionMessage, RenderMessage, SpectateCommand},
+};
+
+use super::{
+    bot::{Analysis, Bot, MoveRequest},
+    human_bot::HumanBot,
+};
+
+// Pace of a watched game, shared by every watched seat
+pub struct SpectatorControl {
+    pub paused: bool,
+    /// Least time a position is shown before the bot to move plays
+    pub delay: Duration,
+    /// Seats a human has taken over
+    pub taken: FxHashSet<Player>,
+    /// Steps asked for that no bot has played yet
+    pub steps: u32,
+}
+
+impl SpectatorControl {
+    pub fn new(delay: Duration) -> Self {
+        SpectatorControl {
+            paused: false,
+            delay,
+            taken: FxHashSet::default(),
+            steps: 0,
+        }
+    }
+
+    pub fn apply(&mut self, command: SpectateCommand) {
+        match command {
+            SpectateCommand::TogglePause => self.paused = !self.paused,
+            SpectateCommand::Step => self.steps += 1,
+            SpectateCommand::Delay(delay) => self.delay = delay,
+            SpectateCommand::TakeOver(player) => {
+                self.taken.insert(player);
+            }
+        }
+    }
+}
+
+// Holds each of `inner`'s moves to the pace set by the window's pause/step/delay
+// controls, and hands the seat to `human` once taken over
+pub struct SpectatorBot {
+    inner: Box<dyn Bot>,
+    human: HumanBot,
+    control: Rc<RefCell<SpectatorControl>>,
+    receiver: Rc<Mutex<Receiver<InteractionMessage>>>,
+}
+
+impl SpectatorBot {
+    pub fn new(
+        inner: Box<dyn Bot>,
+        control: Rc<RefCell<SpectatorControl>>,
+        receiver: Rc<Mutex<Receiver<InteractionMessage>>>,
+        sender: Sender<RenderMessage>,
+    ) -> Self {
+        let human = HumanBot::new(inner.get_own_player().clone(), receiver.clone(), sender)
+            .with_spectator(control.clone());
+        SpectatorBot {
+            inner,
+            human,
+            control,
+            receiver,
+        }
+    }
+
+    fn is_taken(&self) -> bool {
+        self.control.borrow().taken.contains(self.get_own_player())
+    }
+
+    // holds the move until the delay is over or a step is asked for, the position is
+    // already on screen as the game is played logged
+    fn wait_turn(&self) {
+        let shown = Instant::now();
+        loop {
+            if self.is_taken() {
+                return;
+            }
+            let mut control = self.control.borrow_mut();
+            if control.steps > 0 {
+                control.steps -= 1;
+                return;
+            }
+            let timeout = match control.paused {
+                true => None,
+                false => match control.delay.checked_sub(shown.elapsed()) {
+                    Some(left) if !left.is_zero() => Some(left),
+                    _ => return,
+                },
+            };
+            drop(control);
+
+            let receiver = self.receiver.lock().unwrap();
+            let message = match timeout {
+                Some(timeout) => receiver.recv_timeout(timeout),
+                None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            drop(receiver);
+            let mut control = self.control.borrow_mut();
+            match message {
+                Ok(InteractionMessage::Spectate(command)) => control.apply(command),
+                Ok(_) => {}
+                Err(RecvTimeoutError::Timeout) => return,
+                Err(RecvTimeoutError::Disconnected) => {
+                    // the window is gone, finish the game without waiting
+                    control.paused = false;
+                    control.delay = Duration::ZERO;
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl Bot for SpectatorBot {
+    fn get_name(&self) -> String {
+        self.inner.get_name()
+    }
+
+    fn get_own_player(&self) -> &Player {
+        self.inner.get_own_player()
+    }
+
+    fn get_move(&mut self, state: &RefereeState) -> MoveRequest {
+        self.wait_turn();
+        if self.is_taken() {
+            return self.human.get_move(state);
+        }
+        self.inner.get_move(state)
+    }
+
+    fn analyze(&mut self, state: &RefereeState) -> Option<Analysis> {
+        self.inner.analyze(state)
+    }
+}
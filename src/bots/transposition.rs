@@ -0,0 +1,95 @@
+use crate::board::mix;
+
+struct Slot<V> {
+    key: u64,
+    generation: u32,
+    weight: u32,
+    value: V,
+}
+
+// Fixed size hash table for search results keyed by a position hash, one entry per
+// slot. A colliding insert replaces the entry if that one is from an older search or
+// has no more weight (search depth, etc.) behind it than the new one
+pub struct TranspositionTable<V> {
+    slots: Vec<Option<Slot<V>>>,
+    generation: u32,
+    filled: usize,
+}
+
+impl<V> TranspositionTable<V> {
+    pub fn new(capacity: usize) -> Self {
+        let mut slots = Vec::new();
+        slots.resize_with(capacity.max(1), || None);
+        Self {
+            slots,
+            generation: 0,
+            filled: 0,
+        }
+    }
+
+    // entries from earlier searches stay readable but are the first to be replaced
+    pub fn new_search(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    pub fn len(&self) -> usize {
+        self.filled
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.filled == 0
+    }
+
+    fn index(&self, key: u64) -> usize {
+        (mix(key) % self.slots.len() as u64) as usize
+    }
+
+    pub fn get(&self, key: u64) -> Option<&V> {
+        match &self.slots[self.index(key)] {
+            Some(slot) if slot.key == key => Some(&slot.value),
+            _ => None,
+        }
+    }
+
+    // returns whether the value was stored
+    pub fn insert(&mut self, key: u64, weight: u32, value: V) -> bool {
+        let idx = self.index(key);
+        let generation = self.generation;
+        let replace = match &self.slots[idx] {
+            None => {
+                self.filled += 1;
+                true
+            }
+            Some(slot) => slot.key == key || slot.generation != generation || slot.weight <= weight,
+        };
+        if replace {
+            self.slots[idx] = Some(Slot {
+                key,
+                generation,
+                weight,
+                value,
+            });
+        }
+        replace
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replacement_prefers_weight_then_recency() {
+        let mut table = TranspositionTable::new(1);
+        assert!(table.insert(1, 5, "deep"));
+        assert!(!table.insert(2, 3, "shallow"));
+        assert_eq!(table.get(1), Some(&"deep"));
+        assert_eq!(table.get(2), None);
+
+        table.new_search();
+        assert!(table.insert(2, 3, "shallow"));
+        assert_eq!(table.get(1), None);
+        assert_eq!(table.get(2), Some(&"shallow"));
+        assert_eq!(table.len(), 1);
+    }
+}
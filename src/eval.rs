@@ -0,0 +1,809 @@
+use std::{
+    cmp::Ordering,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{self, AtomicU64},
+        Mutex,
+    },
+};
+
+use indicatif::{ParallelProgressIterator, ProgressBar, ProgressStyle};
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::{
+    arena::{GameResult, GameStats, Match, MessageResult},
+    bots::{mcts_bot::score_margin, registry::BotSpec},
+    referee::Player,
+    tilebag::LegalTileBag,
+};
+
+pub fn progress_bar(n: u64) -> ProgressBar {
+    let bar = ProgressBar::new(n);
+    let progress_style = ProgressStyle::with_template(
+        "[{elapsed}/{duration}] {bar:40.green/white} {pos:>7}/{len:7} {msg}",
+    )
+    .unwrap();
+    bar.set_style(progress_style);
+    bar.inc(0);
+    bar
+}
+
+#[derive(Clone, Debug, Default, clap::Args)]
+pub struct GameLogArgs {
+    /// Write one record per game, CSV if the file ends in `.csv`, JSON lines otherwise
+    #[arg(long, value_name = "RECORD_FILE")]
+    pub records: Option<PathBuf>,
+    /// Save the replay of every game into this directory
+    #[arg(long, value_name = "REPLAY_DIR")]
+    pub replay_dir: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct GameRecord {
+    pub seed: u64,
+    pub white: String,
+    pub black: String,
+    pub white_score: u32,
+    pub black_score: u32,
+    /// `white`, `black` or `draw`
+    pub winner: String,
+    pub moves: u32,
+    pub discarded: u32,
+    pub duration_ms: u128,
+    pub white_think_ms: u128,
+    pub black_think_ms: u128,
+    pub replay: Option<PathBuf>,
+}
+
+const CSV_HEADER: &str = "seed,white,black,white_score,black_score,winner,moves,discarded,\
+duration_ms,white_think_ms,black_think_ms,replay";
+
+fn csv_field(field: &str) -> String {
+    format!("\"{}\"", field.replace('"', "\"\""))
+}
+
+impl GameRecord {
+    pub fn new(
+        seed: u64,
+        white: &BotSpec,
+        black: &BotSpec,
+        result: &GameResult,
+        stats: &GameStats,
+        replay: Option<PathBuf>,
+    ) -> Self {
+        let score = |player| *result.player_scores.get(&player).unwrap_or(&0);
+        let think_ms = |player| {
+            stats
+                .think_time
+                .get(&player)
+                .map(|time| time.as_millis())
+                .unwrap_or(0)
+        };
+        let winner = match game_points(result, &Player::White).partial_cmp(&0.5) {
+            Some(Ordering::Greater) => "white",
+            Some(Ordering::Less) => "black",
+            _ => "draw",
+        };
+        Self {
+            seed,
+            white: white.to_string(),
+            black: black.to_string(),
+            white_score: score(Player::White),
+            black_score: score(Player::Black),
+            winner: winner.to_string(),
+            moves: stats.moves,
+            discarded: stats.discarded,
+            duration_ms: stats.duration.as_millis(),
+            white_think_ms: think_ms(Player::White),
+            black_think_ms: think_ms(Player::Black),
+            replay,
+        }
+    }
+
+    pub fn to_csv(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{}",
+            self.seed,
+            csv_field(&self.white),
+            csv_field(&self.black),
+            self.white_score,
+            self.black_score,
+            self.winner,
+            self.moves,
+            self.discarded,
+            self.duration_ms,
+            self.white_think_ms,
+            self.black_think_ms,
+            self.replay
+                .as_ref()
+                .map(|path| csv_field(&path.to_string_lossy()))
+                .unwrap_or_default(),
+        )
+    }
+}
+
+// Where eval games get written to, shared across the rayon pool
+#[derive(Default)]
+pub struct GameLog {
+    writer: Option<Mutex<BufWriter<File>>>,
+    csv: bool,
+    replay_dir: Option<PathBuf>,
+    games: AtomicU64,
+}
+
+impl GameLog {
+    pub fn new(args: GameLogArgs) -> MessageResult<Self> {
+        let csv = args
+            .records
+            .as_ref()
+            .map(|path| path.extension().map(|ext| ext == "csv").unwrap_or(false))
+            .unwrap_or(false);
+        let writer = match args.records {
+            Some(path) => {
+                let file = File::create(path).or(Err("Failed to create record file"))?;
+                let mut writer = BufWriter::new(file);
+                if csv {
+                    writeln!(writer, "{CSV_HEADER}").or(Err("Failed to write to file"))?;
+                }
+                Some(Mutex::new(writer))
+            }
+            None => None,
+        };
+        if let Some(dir) = &args.replay_dir {
+            fs::create_dir_all(dir).or(Err("Failed to create replay directory"))?;
+        }
+        Ok(Self {
+            writer,
+            csv,
+            replay_dir: args.replay_dir,
+            games: AtomicU64::new(0),
+        })
+    }
+
+    fn write(&self, record: &GameRecord) -> MessageResult<()> {
+        let Some(writer) = &self.writer else {
+            return Ok(());
+        };
+        let line = if self.csv {
+            record.to_csv()
+        } else {
+            serde_json::to_string(record).or(Err("Failed to serialize record"))?
+        };
+        let mut writer = writer.lock().unwrap();
+        writeln!(writer, "{line}").or(Err("Failed to write to file"))?;
+        // keep the file useful if a long eval gets interrupted
+        writer.flush().or(Err("Failed to write to file"))
+    }
+}
+
+// Plays one eval game on the tile order fixed by `seed` and logs it
+pub fn play_game(
+    white: &BotSpec,
+    black: &BotSpec,
+    seed: u64,
+    log: &GameLog,
+) -> MessageResult<GameResult> {
+    let replay = log.replay_dir.as_ref().map(|dir| {
+        let idx = log.games.fetch_add(1, atomic::Ordering::Relaxed);
+        dir.join(format!("game_{idx:06}_{seed}.replay"))
+    });
+    let (result, stats) = Match::play_with_stats(
+        vec![white.build(Player::White)?, black.build(Player::Black)?],
+        LegalTileBag::seeded(seed).into(),
+        replay.clone(),
+        None,
+        None,
+        None,
+    )?;
+    log.write(&GameRecord::new(
+        seed, white, black, &result, &stats, replay,
+    ))?;
+    Ok(result)
+}
+
+// 1 for a win, 0.5 for a draw, 0 for a loss
+pub fn game_points(result: &GameResult, player: &Player) -> f64 {
+    let winners = result.get_winners();
+    if !winners.contains(player) {
+        0.0
+    } else if winners.len() > 1 {
+        0.5
+    } else {
+        1.0
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Rating {
+    pub elo: f64,
+    /// Half width of the 95% confidence interval
+    pub ci95: f64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Crosstable {
+    pub bots: Vec<String>,
+    /// points[i][j] is what bot i scored against bot j, over both seats
+    pub points: Vec<Vec<f64>>,
+    pub games: Vec<Vec<u32>>,
+    pub ratings: Vec<Rating>,
+}
+
+// Every bot plays every other `games` times as white and `games` times as black
+pub fn round_robin(specs: &[BotSpec], games: u32, log: &GameLog) -> Crosstable {
+    let n = specs.len();
+    let fixtures: Vec<(usize, usize)> = (0..n)
+        .flat_map(|i| (0..n).filter(move |j| *j != i).map(move |j| (i, j)))
+        .flat_map(|pair| (0..games).map(move |_| pair))
+        .collect();
+
+    let results: Vec<(usize, usize, f64)> = fixtures
+        .par_iter()
+        .progress_with(progress_bar(fixtures.len() as u64))
+        .map(|(white, black)| {
+            let result = play_game(&specs[*white], &specs[*black], rand::random(), log).unwrap();
+            (*white, *black, game_points(&result, &Player::White))
+        })
+        .collect();
+
+    let mut points = vec![vec![0.0; n]; n];
+    let mut played = vec![vec![0; n]; n];
+    for (white, black, white_points) in results {
+        points[white][black] += white_points;
+        points[black][white] += 1.0 - white_points;
+        played[white][black] += 1;
+        played[black][white] += 1;
+    }
+    let ratings = bradley_terry(&points, &played);
+    Crosstable {
+        bots: specs.iter().map(|spec| spec.to_string()).collect(),
+        points,
+        games: played,
+        ratings,
+    }
+}
+
+// Bradley-Terry strengths by minorization-maximization, reported on the Elo scale
+// with mean 0. A virtual draw per pairing keeps perfect scores finite.
+pub fn bradley_terry(points: &[Vec<f64>], games: &[Vec<u32>]) -> Vec<Rating> {
+    let n = points.len();
+    let prior = |i: usize, j: usize| if i != j && games[i][j] > 0 { 1.0 } else { 0.0 };
+    let wins: Vec<f64> = (0..n)
+        .map(|i| (0..n).map(|j| points[i][j] + 0.5 * prior(i, j)).sum())
+        .collect();
+    let count = |i: usize, j: usize| games[i][j] as f64 + prior(i, j);
+
+    let mut strength = vec![1.0; n];
+    for _ in 0..1000 {
+        let mut next: Vec<f64> = (0..n)
+            .map(|i| {
+                let denominator: f64 = (0..n)
+                    .filter(|j| *j != i)
+                    .map(|j| count(i, j) / (strength[i] + strength[j]))
+                    .sum();
+                if denominator > 0.0 {
+                    wins[i] / denominator
+                } else {
+                    strength[i]
+                }
+            })
+            .collect();
+        let log_mean = next.iter().map(|s| s.ln()).sum::<f64>() / n as f64;
+        next.iter_mut().for_each(|s| *s /= log_mean.exp());
+        let change = next
+            .iter()
+            .zip(&strength)
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f64::max);
+        strength = next;
+        if change < 1e-9 {
+            break;
+        }
+    }
+
+    let scale = 400.0 / std::f64::consts::LN_10;
+    (0..n)
+        .map(|i| {
+            let information: f64 = (0..n)
+                .filter(|j| *j != i)
+                .map(|j| {
+                    let total = strength[i] + strength[j];
+                    count(i, j) * strength[i] * strength[j] / (total * total)
+                })
+                .sum();
+            Rating {
+                elo: scale * strength[i].ln(),
+                ci95: if information > 0.0 {
+                    1.96 * scale / information.sqrt()
+                } else {
+                    f64::INFINITY
+                },
+            }
+        })
+        .collect()
+}
+
+impl Crosstable {
+    // bot indices, strongest first
+    pub fn ranking(&self) -> Vec<usize> {
+        let mut out: Vec<usize> = (0..self.bots.len()).collect();
+        out.sort_by(|l, r| {
+            self.ratings[*r]
+                .elo
+                .partial_cmp(&self.ratings[*l].elo)
+                .unwrap()
+        });
+        out
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("bot,elo,ci95,points,games");
+        for bot in &self.bots {
+            out += &format!(",\"{bot}\"");
+        }
+        out += "\n";
+        for i in self.ranking() {
+            out += &format!(
+                "\"{}\",{:.1},{:.1},{},{}",
+                self.bots[i],
+                self.ratings[i].elo,
+                self.ratings[i].ci95,
+                self.points[i].iter().sum::<f64>(),
+                self.games[i].iter().sum::<u32>()
+            );
+            for j in 0..self.bots.len() {
+                out += &format!(",{}", self.points[i][j]);
+            }
+            out += "\n";
+        }
+        out
+    }
+
+    pub fn print(&self) {
+        for (rank, i) in self.ranking().into_iter().enumerate() {
+            let games: u32 = self.games[i].iter().sum();
+            let points: f64 = self.points[i].iter().sum();
+            println!(
+                "{:>2}. {:<30} {:>7.1} +/- {:<6.1} {points}/{games}",
+                rank + 1,
+                self.bots[i],
+                self.ratings[i].elo,
+                self.ratings[i].ci95,
+            );
+        }
+    }
+}
+
+// Both games of a seed, seen from bot `a`
+#[derive(Clone, Debug)]
+pub struct PairedGame {
+    pub seed: u64,
+    pub margin_as_white: f64,
+    pub margin_as_black: f64,
+    pub points: f64,
+}
+
+// `a` and `b` play each tile sequence twice with seats swapped, cancelling out
+// first move advantage and tile luck
+pub fn paired_match(
+    a: &BotSpec,
+    b: &BotSpec,
+    pairs: u32,
+    first_seed: u64,
+    log: &GameLog,
+) -> MessageResult<PairedStats> {
+    let games: Vec<PairedGame> = (0..pairs as u64)
+        .into_par_iter()
+        .progress_with(progress_bar(pairs as u64))
+        .map(|i| {
+            let seed = first_seed.wrapping_add(i);
+            let as_white = play_game(a, b, seed, log)?;
+            let as_black = play_game(b, a, seed, log)?;
+            Ok(PairedGame {
+                seed,
+                margin_as_white: score_margin(&as_white.player_scores, &Player::White),
+                margin_as_black: score_margin(&as_black.player_scores, &Player::Black),
+                points: game_points(&as_white, &Player::White)
+                    + game_points(&as_black, &Player::Black),
+            })
+        })
+        .collect::<MessageResult<_>>()?;
+    Ok(PairedStats::new(games))
+}
+
+#[derive(Clone, Debug)]
+pub struct PairedStats {
+    pub games: Vec<PairedGame>,
+    /// Mean over pairs of `a`'s average margin in the two games
+    pub mean_margin: f64,
+    pub margin_se: f64,
+    /// Pairs `a` won on points, lost on points and split
+    pub pair_wins: u32,
+    pub pair_losses: u32,
+    pub pair_ties: u32,
+    /// Two sided sign test over the decided pairs
+    pub sign_test_p: f64,
+}
+
+impl PairedStats {
+    pub fn new(games: Vec<PairedGame>) -> Self {
+        let margins: Vec<f64> = games
+            .iter()
+            .map(|game| (game.margin_as_white + game.margin_as_black) / 2.0)
+            .collect();
+        let n = margins.len() as f64;
+        let mean_margin = margins.iter().sum::<f64>() / n.max(1.0);
+        let margin_se = if margins.len() > 1 {
+            let variance = margins
+                .iter()
+                .map(|m| (m - mean_margin).powi(2))
+                .sum::<f64>()
+                / (n - 1.0);
+            (variance / n).sqrt()
+        } else {
+            f64::INFINITY
+        };
+
+        let pair_wins = games.iter().filter(|game| game.points > 1.0).count() as u32;
+        let pair_losses = games.iter().filter(|game| game.points < 1.0).count() as u32;
+        let pair_ties = games.len() as u32 - pair_wins - pair_losses;
+        Self {
+            sign_test_p: sign_test(pair_wins, pair_losses),
+            games,
+            mean_margin,
+            margin_se,
+            pair_wins,
+            pair_losses,
+            pair_ties,
+        }
+    }
+
+    pub fn print(&self, a: &BotSpec, b: &BotSpec) {
+        println!(
+            "{a} vs {b} over {} seat-swapped pairs\n\
+             {a} mean margin {:.2} +/- {:.2} (standard error)\n\
+             pairs won {}, lost {}, split {}, sign test p = {:.4}",
+            self.games.len(),
+            self.mean_margin,
+            self.margin_se,
+            self.pair_wins,
+            self.pair_losses,
+            self.pair_ties,
+            self.sign_test_p,
+        );
+    }
+}
+
+// probability of a split at least this lopsided between two equal bots
+pub fn sign_test(wins: u32, losses: u32) -> f64 {
+    let n = wins + losses;
+    if n == 0 {
+        return 1.0;
+    }
+    let extreme = wins.min(losses);
+    // ln(C(n, k) / 2^n), built up from k = 0
+    let mut log_pmf = -(n as f64) * std::f64::consts::LN_2;
+    let mut tail = 0.0;
+    for k in 0..=extreme {
+        tail += log_pmf.exp();
+        log_pmf += ((n - k) as f64).ln() - ((k + 1) as f64).ln();
+    }
+    (2.0 * tail).min(1.0)
+}
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct SprtConfig {
+    /// Elo difference of the null hypothesis
+    #[arg(long, default_value_t = 0.0)]
+    pub elo0: f64,
+    /// Elo difference of the alternative hypothesis
+    #[arg(long, default_value_t = 10.0)]
+    pub elo1: f64,
+    /// Chance of accepting elo1 when elo0 holds
+    #[arg(long, default_value_t = 0.05)]
+    pub alpha: f64,
+    /// Chance of accepting elo0 when elo1 holds
+    #[arg(long, default_value_t = 0.05)]
+    pub beta: f64,
+}
+
+impl SprtConfig {
+    pub fn validate(&self) -> MessageResult<()> {
+        if !(self.alpha > 0.0 && self.alpha < 1.0 && self.beta > 0.0 && self.beta < 1.0) {
+            return Err("alpha and beta must be between 0 and 1");
+        }
+        if self.elo1 <= self.elo0 {
+            return Err("elo1 must be greater than elo0");
+        }
+        Ok(())
+    }
+
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SprtDecision {
+    /// LLR crossed the upper bound, the first bot is elo1 stronger
+    AcceptH1,
+    /// LLR crossed the lower bound, the first bot is at most elo0 stronger
+    AcceptH0,
+    /// Ran out of games first
+    Inconclusive,
+}
+
+#[derive(Clone, Debug)]
+pub struct SprtResult {
+    pub decision: SprtDecision,
+    pub llr: f64,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    /// Seat-swapped pairs by the first bot's points over both games, 0 to 2 in halves
+    pub pairs: [u32; 5],
+}
+
+fn expected_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+// log-likelihood ratio of elo1 over elo0 given pentanomial pair results, using the
+// normal approximation of the mean pair score. Both games of a pair share a tile
+// sequence, so only whole pairs are independent samples. Empty buckets count as half
+// a pair so an unbroken run of won pairs still has a variance.
+pub fn sprt_llr(pairs: &[u32; 5], elo0: f64, elo1: f64) -> f64 {
+    if pairs.iter().sum::<u32>() == 0 {
+        return 0.0;
+    }
+    let counts = pairs.map(|c| if c == 0 { 0.5 } else { c as f64 });
+    let scores = [0.0, 0.25, 0.5, 0.75, 1.0];
+    let n: f64 = counts.iter().sum();
+    let mean = counts.iter().zip(scores).map(|(c, s)| c * s).sum::<f64>() / n;
+    let variance = counts
+        .iter()
+        .zip(scores)
+        .map(|(c, s)| c * (s - mean).powi(2))
+        .sum::<f64>()
+        / n;
+    let (s0, s1) = (expected_score(elo0), expected_score(elo1));
+    (s1 - s0) * (2.0 * mean - s0 - s1) * n / (2.0 * variance)
+}
+
+// Plays seat-swapped pairs of `a` against `b` in parallel batches, starting from
+// `first_seed`, until the sequential probability ratio test decides or `max_games`
+// is reached
+pub fn sprt(
+    a: &BotSpec,
+    b: &BotSpec,
+    config: &SprtConfig,
+    max_games: u32,
+    first_seed: u64,
+    log: &GameLog,
+) -> MessageResult<SprtResult> {
+    config.validate()?;
+    let (lower, upper) = config.bounds();
+    let bar = progress_bar(max_games as u64);
+    let threads = rayon::current_num_threads() as u32;
+    let mut seed = first_seed;
+    let mut result = SprtResult {
+        decision: SprtDecision::Inconclusive,
+        llr: 0.0,
+        wins: 0,
+        draws: 0,
+        losses: 0,
+        pairs: [0; 5],
+    };
+    let mut played = 0;
+    loop {
+        // only whole pairs are played, so an odd game left over is never started
+        let batch = threads.min((max_games - played) / 2) as u64;
+        if batch == 0 {
+            break;
+        }
+        let pairs: Vec<[f64; 2]> = (0..batch)
+            .into_par_iter()
+            .map(|i| {
+                let seed = seed.wrapping_add(i);
+                let as_white = play_game(a, b, seed, log)?;
+                let as_black = play_game(b, a, seed, log)?;
+                Ok([
+                    game_points(&as_white, &Player::White),
+                    game_points(&as_black, &Player::Black),
+                ])
+            })
+            .collect::<MessageResult<_>>()?;
+        seed = seed.wrapping_add(batch);
+        for points in pairs {
+            for point in points {
+                match point.partial_cmp(&0.5) {
+                    Some(Ordering::Greater) => result.wins += 1,
+                    Some(Ordering::Less) => result.losses += 1,
+                    _ => result.draws += 1,
+                }
+            }
+            result.pairs[(2.0 * (points[0] + points[1])) as usize] += 1;
+        }
+        played = result.wins + result.draws + result.losses;
+        bar.set_position(played as u64);
+        result.llr = sprt_llr(&result.pairs, config.elo0, config.elo1);
+        bar.set_message(format!("LLR {:.2} [{lower:.2}, {upper:.2}]", result.llr));
+        if result.llr >= upper {
+            result.decision = SprtDecision::AcceptH1;
+            break;
+        }
+        if result.llr <= lower {
+            result.decision = SprtDecision::AcceptH0;
+            break;
+        }
+    }
+    bar.finish();
+    Ok(result)
+}
+
+impl SprtResult {
+    pub fn print(&self, a: &BotSpec, b: &BotSpec, config: &SprtConfig) {
+        let (lower, upper) = config.bounds();
+        let verdict = match self.decision {
+            SprtDecision::AcceptH1 => format!("H1 accepted, {a} is stronger by {}", config.elo1),
+            SprtDecision::AcceptH0 => {
+                format!("H0 accepted, {a} is not stronger than {}", config.elo0)
+            }
+            SprtDecision::Inconclusive => "inconclusive, game limit reached".to_string(),
+        };
+        println!(
+            "SPRT {a} vs {b}: {verdict}\n\
+             LLR {:.2} [{lower:.2}, {upper:.2}] after {} games (W {} D {} L {})\n\
+             pairs by points 0-2 in halves: {:?}",
+            self.llr,
+            self.wins + self.draws + self.losses,
+            self.wins,
+            self.draws,
+            self.losses,
+            self.pairs,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arena::Replay;
+
+    #[test]
+    fn ratings_follow_results() {
+        let points = vec![
+            vec![0.0, 15.0, 20.0],
+            vec![5.0, 0.0, 12.0],
+            vec![0.0, 8.0, 0.0],
+        ];
+        let games = vec![vec![0, 20, 20], vec![20, 0, 20], vec![20, 20, 0]];
+        let ratings = bradley_terry(&points, &games);
+        assert!(ratings[0].elo > ratings[1].elo);
+        assert!(ratings[1].elo > ratings[2].elo);
+        assert!(ratings.iter().map(|r| r.elo).sum::<f64>().abs() < 1e-6);
+        assert!(ratings.iter().all(|r| r.ci95.is_finite()));
+
+        let even = bradley_terry(
+            &[vec![0.0, 5.0], vec![5.0, 0.0]],
+            &[vec![0, 10], vec![10, 0]],
+        );
+        assert!(even[0].elo.abs() < 1e-6);
+    }
+
+    #[test]
+    fn sign_test_bounds() {
+        assert_eq!(sign_test(5, 5), 1.0);
+        assert!((sign_test(10, 0) - 2.0 / 1024.0).abs() < 1e-12);
+        assert!(sign_test(60, 40) > sign_test(70, 30));
+    }
+
+    #[test]
+    fn paired_stats_from_margins() {
+        let game = |seed, white: f64, black: f64, points| PairedGame {
+            seed,
+            margin_as_white: white,
+            margin_as_black: black,
+            points,
+        };
+        let stats = PairedStats::new(vec![
+            game(0, 10.0, -2.0, 1.0),
+            game(1, 6.0, 2.0, 2.0),
+            game(2, -4.0, 0.0, 0.0),
+        ]);
+        assert_eq!(stats.mean_margin, 2.0);
+        assert!((stats.margin_se - 2.0).abs() < 1e-12);
+        assert_eq!(
+            (stats.pair_wins, stats.pair_losses, stats.pair_ties),
+            (1, 1, 1)
+        );
+        assert_eq!(stats.sign_test_p, 1.0);
+    }
+
+    #[test]
+    fn sprt_llr_direction() {
+        let config = SprtConfig {
+            elo0: 0.0,
+            elo1: 10.0,
+            alpha: 0.05,
+            beta: 0.05,
+        };
+        let (lower, upper) = config.bounds();
+        assert!((upper + lower).abs() < 1e-12);
+        assert!(sprt_llr(&[0, 100, 500, 300, 100], 0.0, 10.0) > upper);
+        assert!(sprt_llr(&[100, 300, 500, 100, 0], 0.0, 10.0) < lower);
+        assert!(sprt_llr(&[0, 0, 0, 0, 5], 0.0, 10.0).is_finite());
+        assert_eq!(sprt_llr(&[0; 5], 0.0, 10.0), 0.0);
+    }
+
+    #[test]
+    fn sprt_checks_config_and_game_limit() {
+        let random: BotSpec = "random".parse().unwrap();
+        let config = SprtConfig {
+            elo0: 0.0,
+            elo1: 10.0,
+            alpha: 0.05,
+            beta: 0.05,
+        };
+        for bad in [
+            SprtConfig {
+                alpha: 0.0,
+                ..config.clone()
+            },
+            SprtConfig {
+                beta: 1.0,
+                ..config.clone()
+            },
+            SprtConfig {
+                elo1: 0.0,
+                ..config.clone()
+            },
+        ] {
+            assert!(sprt(&random, &random, &bad, 2, 0, &GameLog::default()).is_err());
+        }
+        let result = sprt(&random, &random, &config, 3, 0, &GameLog::default()).unwrap();
+        assert_eq!(result.wins + result.draws + result.losses, 2);
+    }
+
+    #[test]
+    fn game_log_writes_records_and_replays() {
+        let dir = std::env::temp_dir().join(format!("crabcassonne_log_{}", rand::random::<u32>()));
+        let records = dir.join("games.csv");
+        fs::create_dir_all(&dir).unwrap();
+        let log = GameLog::new(GameLogArgs {
+            records: Some(records.clone()),
+            replay_dir: Some(dir.join("replays")),
+        })
+        .unwrap();
+        let random: BotSpec = "random".parse().unwrap();
+        let result = play_game(&random, &random, 5, &log).unwrap();
+        drop(log);
+
+        let csv = fs::read_to_string(&records).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let score = result.player_scores.get(&Player::White).unwrap();
+        assert!(lines[1].starts_with(&format!("5,\"random\",\"random\",{score},")));
+        let replay = Replay::from_path(dir.join("replays/game_000000_5.replay")).unwrap();
+        assert_eq!(replay.replay(false), result);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn round_robin_swaps_seats() {
+        let specs: Vec<BotSpec> = ["random", "greedy"]
+            .iter()
+            .map(|spec| spec.parse().unwrap())
+            .collect();
+        let table = round_robin(&specs, 1, &GameLog::default());
+        assert_eq!(table.games, vec![vec![0, 2], vec![2, 0]]);
+        assert_eq!(table.points[0][1] + table.points[1][0], 2.0);
+        assert_eq!(table.to_csv().lines().count(), 3);
+    }
+}
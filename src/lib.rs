@@ -1,9 +1,13 @@
 pub mod arena;
 pub mod board;
+pub mod book;
 pub mod bots;
 pub mod coord_map;
+pub mod eval;
+pub mod net;
 pub mod referee;
 pub mod render;
 pub mod render_tile;
 pub mod tile;
+pub mod tile_art;
 pub mod tilebag;
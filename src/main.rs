@@ -4,22 +4,32 @@ use mimalloc::MiMalloc;
 static GLOBAL: MiMalloc = MiMalloc;
 
 use std::{
+    cell::RefCell,
     path::PathBuf,
     rc::Rc,
-    sync::{mpsc::channel, Mutex},
+    sync::{mpsc::channel, Arc, Mutex},
     thread,
+    time::Duration,
 };
 
 use crabcassonne::{
     arena::{random_match, Match, Replay},
+    book::OpeningBook,
     bots::{
-        bot::Bot, greedy_bot::GreedyBot, human_bot::HumanBot, mcts_bot::MCTSBot,
-        random_bot::RandomBot, shallow_bot::ShallowBot,
+        bot::{Bot, MoveRequest},
+        explain_bot::ExplainBot,
+        human_bot::HumanBot,
+        registry::{BotSpec, SPEC_HELP},
+        spectator_bot::{SpectatorBot, SpectatorControl},
     },
+    eval::{
+        paired_match, play_game, progress_bar, round_robin, sprt, GameLog, GameLogArgs, SprtConfig,
+    },
+    net::{network::Network, self_play},
     referee::Player,
     render::{InteractionMessage, MyApp, RenderMessage},
 };
-use indicatif::{ParallelProgressIterator, ProgressBar, ProgressStyle};
+use indicatif::ParallelProgressIterator;
 use rayon::prelude::*;
 
 use clap::{Parser, Subcommand};
@@ -36,12 +46,46 @@ struct Cli {
 enum Commands {
     /// Start a game
     Play {
-        /// Number of human players to start a game with
-        #[arg(short, long, default_value_t = 1, value_parser = clap::value_parser!(u8).range(0..3))]
-        players: u8,
+        /// White player, moves first
+        #[arg(short, long, default_value = "human", long_help = SPEC_HELP)]
+        white: BotSpec,
+        /// Black player
+        #[arg(short, long, default_value = "mcts", long_help = SPEC_HELP)]
+        black: BotSpec,
         /// Sets a destination file for replay
         #[arg(short, long, value_name = "REPLAY_FILE")]
         output: Option<PathBuf>,
+        /// Print each bot's analysis of its moves
+        #[arg(short, long, default_value_t = false)]
+        explain: bool,
+        /// Bot whose analysis is shown to human players as a heatmap over the board
+        #[arg(short, long, long_help = SPEC_HELP)]
+        advisor: Option<BotSpec>,
+        /// Watch the bots' moves in the window, with controls to pause them or take a seat
+        #[arg(long, default_value_t = false)]
+        watch: bool,
+        /// Milliseconds each position is shown before a watched bot moves
+        #[arg(long, default_value_t = 800)]
+        delay: u64,
+    },
+    /// Rank every legal move of a position taken from a replay file
+    Analyze {
+        /// Replay holding the position, positions are only saved as replays
+        #[arg(short, long, value_name = "REPLAY_FILE")]
+        input: PathBuf,
+        /// Number of replay moves played before the position, the whole replay if unset
+        #[arg(short, long = "move")]
+        move_number: Option<usize>,
+        /// Bot doing the analysis
+        #[arg(short, long, default_value = "mcts:iters=500", long_help = SPEC_HELP)]
+        bot: BotSpec,
+        /// Search iterations, overrides the spec for mcts, net and shallow bots
+        #[arg(long)]
+        budget: Option<u32>,
+        /// How far below the best move the replay's move has to be to count as a blunder,
+        /// in score margin points where the bot estimates them
+        #[arg(long, default_value_t = 5.0)]
+        blunder: f64,
     },
     /// Replay a replay file
     Replay {
@@ -55,17 +99,97 @@ enum Commands {
         #[command(subcommand)]
         demo: Demo,
     },
+    /// Train a policy/value network by self-play
+    Train {
+        /// Where the network is saved after every generation
+        #[arg(short, long, value_name = "MODEL_FILE")]
+        output: PathBuf,
+        /// Network to continue training from, a fresh one is created otherwise
+        #[arg(short, long, value_name = "MODEL_FILE")]
+        input: Option<PathBuf>,
+        #[arg(short, long, default_value_t = 10)]
+        generations: u32,
+        /// Self-play games per generation
+        #[arg(short, long, default_value_t = 20)]
+        num_games: u32,
+        /// MCTS iterations per move during self-play
+        #[arg(long, default_value_t = 50)]
+        iterations: u32,
+        #[arg(long, default_value_t = 2)]
+        epochs: u32,
+        #[arg(long, default_value_t = 0.005)]
+        learning_rate: f32,
+        /// Hidden layer width of a fresh network
+        #[arg(long, default_value_t = 64)]
+        hidden: usize,
+    },
+    /// Build or extend an opening book from replays and self-play
+    Book {
+        /// Where the book is saved
+        #[arg(short, long, value_name = "BOOK_FILE")]
+        output: PathBuf,
+        /// Book to extend, a fresh one is created otherwise
+        #[arg(short, long, value_name = "BOOK_FILE")]
+        input: Option<PathBuf>,
+        /// Replay files, or directories of them, to add
+        #[arg(short, long, num_args = 1..)]
+        replays: Vec<PathBuf>,
+        /// Bot that plays the self-play openings, see `play --help` for the format
+        #[arg(short, long, long_help = SPEC_HELP)]
+        self_play: Option<BotSpec>,
+        /// Self-play games to add
+        #[arg(short, long, default_value_t = 100)]
+        num_games: u32,
+        /// Tiles on the board up to which positions are kept, fresh books only
+        #[arg(short, long, default_value_t = 8)]
+        plies: u32,
+    },
 }
 
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 enum Demo {
     /// [Benchmark] pits bots against eachother in multithreaded matches
     Threaded {
         #[arg(short, long, default_value_t = 10_000)]
         num_games: u32,
-        /// Simulate games between non_random players
-        #[arg(short, long, default_value_t = false)]
-        slow: bool,
+        /// White player, moves first
+        #[arg(short, long, default_value = "random", long_help = SPEC_HELP)]
+        white: BotSpec,
+        /// Black player
+        #[arg(short, long, default_value = "random", long_help = SPEC_HELP)]
+        black: BotSpec,
+        /// Play each tile sequence twice with seats swapped, `num_games` counts pairs
+        #[arg(long, default_value_t = false)]
+        paired: bool,
+        /// Seed of the first paired tile sequence, random if unset
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Stop early once a sequential probability ratio test of white against black
+        /// decides, `num_games` becomes the game limit. Always plays seat-swapped pairs.
+        #[arg(long, default_value_t = false, conflicts_with = "paired")]
+        sprt: bool,
+        #[command(flatten)]
+        sprt_config: SprtConfig,
+        #[command(flatten)]
+        log: GameLogArgs,
+    },
+    /// Round robin between several bots with swapped seats, rated by Bradley-Terry
+    Tournament {
+        /// Bot specs taking part, see `play --help` for the format
+        #[arg(short, long, num_args = 2.., required = true)]
+        bots: Vec<BotSpec>,
+        /// Games per ordered pairing, every pairing is also played with seats swapped
+        #[arg(short, long, default_value_t = 10)]
+        num_games: u32,
+        /// Write the crosstable as CSV
+        #[arg(long, value_name = "CSV_FILE")]
+        csv: Option<PathBuf>,
+        /// Write the crosstable and ratings as JSON
+        #[arg(long, value_name = "JSON_FILE")]
+        json: Option<PathBuf>,
+        #[command(flatten)]
+        log: GameLogArgs,
     },
     /// [Benchmark] pits random-move bots against eachother in a single thread
     Random {
@@ -78,51 +202,220 @@ fn main() {
     //    env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
     let cli = Cli::parse();
     match cli.command {
-        Commands::Play { players, output } => demo_p(players, output),
+        Commands::Play {
+            white,
+            black,
+            output,
+            explain,
+            advisor,
+            watch,
+            delay,
+        } => demo_p(white, black, output, explain, advisor, watch, delay),
+        Commands::Analyze {
+            input,
+            move_number,
+            bot,
+            budget,
+            blunder,
+        } => {
+            let bot = match budget {
+                Some(budget) => match bot.with_budget(budget) {
+                    Ok(bot) => bot,
+                    Err(err) => {
+                        println!("{err}");
+                        return;
+                    }
+                },
+                None => bot,
+            };
+            analyze(input, move_number, bot, blunder)
+        }
         Commands::Replay { input, headless } => {
             let replay = Replay::from_path(input).unwrap();
             let result = replay.replay(!headless);
             result.print(FxHashMap::default());
         }
         Commands::Eval { demo } => match demo {
-            Demo::Threaded { num_games, slow } => demo_threaded(num_games, !slow),
+            Demo::Threaded {
+                num_games,
+                white,
+                black,
+                paired,
+                seed,
+                sprt: use_sprt,
+                sprt_config,
+                log,
+            } => {
+                if let Err(err) = white.require_headless().and(black.require_headless()) {
+                    println!("{err}");
+                    return;
+                }
+                let log = GameLog::new(log).unwrap();
+                let seed = seed.unwrap_or_else(rand::random);
+                if use_sprt {
+                    match sprt(&white, &black, &sprt_config, num_games, seed, &log) {
+                        Ok(result) => result.print(&white, &black, &sprt_config),
+                        Err(err) => println!("SPRT failed: {err}"),
+                    }
+                } else if paired {
+                    match paired_match(&white, &black, num_games, seed, &log) {
+                        Ok(stats) => stats.print(&white, &black),
+                        Err(err) => println!("paired match failed: {err}"),
+                    }
+                } else {
+                    demo_threaded(num_games, white, black, &log)
+                }
+            }
+            Demo::Tournament {
+                bots,
+                num_games,
+                csv,
+                json,
+                log,
+            } => tournament(bots, num_games, csv, json, log),
             Demo::Random { num_games } => random_match(num_games.into()),
         },
+        Commands::Train {
+            output,
+            input,
+            generations,
+            num_games,
+            iterations,
+            epochs,
+            learning_rate,
+            hidden,
+        } => {
+            let mut net = match input {
+                Some(path) => Network::from_path(path).unwrap(),
+                None => Network::new(hidden),
+            };
+            for generation in 0..generations {
+                let shared = Arc::new(net.clone());
+                let mut samples = self_play::generate(&shared, num_games, iterations);
+                let loss = self_play::train(&mut net, &mut samples, epochs, learning_rate);
+                println!(
+                    "generation {generation}: {} positions, loss {loss:.3}",
+                    samples.len()
+                );
+                net.save(output.clone()).unwrap();
+            }
+        }
+        Commands::Book {
+            output,
+            input,
+            replays,
+            self_play,
+            num_games,
+            plies,
+        } => {
+            let mut book = match input {
+                Some(path) => OpeningBook::from_path(path).unwrap(),
+                None => OpeningBook::new(plies),
+            };
+            for path in replays {
+                if path.is_dir() {
+                    book.add_replay_dir(path).unwrap();
+                } else {
+                    book.add_replay(&Replay::from_path(path).unwrap()).unwrap();
+                }
+            }
+            if let Some(spec) = self_play {
+                book.add_self_play(&spec, num_games).unwrap();
+            }
+            println!("{} positions", book.len());
+            book.save(output).unwrap();
+        }
     }
 }
 
-fn demo_p(player_ct: u8, record: Option<PathBuf>) {
+fn demo_p(
+    white: BotSpec,
+    black: BotSpec,
+    record: Option<PathBuf>,
+    explain: bool,
+    advisor: Option<BotSpec>,
+    watch: bool,
+    delay: u64,
+) {
+    if advisor
+        .as_ref()
+        .map(|spec| spec.is_human())
+        .unwrap_or(false)
+    {
+        println!("the advisor has to be a bot");
+        return;
+    }
     let (input_sender, input_receiver) = channel::<RenderMessage>();
     let (sender, receiver) = channel::<InteractionMessage>();
+    let has_human = white.is_human() || black.is_human();
+    let delay = Duration::from_millis(delay);
+    let watched_seats: Vec<Player> = [(&white, Player::White), (&black, Player::Black)]
+        .into_iter()
+        .filter(|(spec, _)| watch && !spec.is_human())
+        .map(|(_, player)| player)
+        .collect();
 
     let handle = thread::spawn(move || {
         let receiver_mutex = Rc::new(Mutex::new(receiver));
-        let bot_w: Box<dyn Bot> = if player_ct > 0 {
-            Box::new(HumanBot::new(
-                Player::White,
+        let control = Rc::new(RefCell::new(SpectatorControl::new(delay)));
+        let build = |spec: &BotSpec, player: Player| -> Box<dyn Bot> {
+            if spec.is_human() {
+                let bot =
+                    HumanBot::new(player.clone(), receiver_mutex.clone(), input_sender.clone());
+                let bot = match watch {
+                    true => bot.with_spectator(control.clone()),
+                    false => bot,
+                };
+                let book = spec.book.clone();
+                let mut advisor = advisor.as_ref().map(|spec| spec.build(player).unwrap());
+                if book.is_none() && advisor.is_none() {
+                    return Box::new(bot);
+                }
+                // the book while in it, the advisor's analysis after
+                return Box::new(bot.with_hints(Box::new(move |state| {
+                    let hints = book.as_ref().map(|book| book.hints(state));
+                    match hints {
+                        Some(hints) if !hints.is_empty() => hints,
+                        _ => advisor
+                            .as_mut()
+                            .and_then(|advisor| advisor.analyze(state))
+                            .map(|analysis| analysis.hints())
+                            .unwrap_or_default(),
+                    }
+                })));
+            }
+            let bot: Box<dyn Bot> = if explain {
+                Box::new(ExplainBot::new(spec.build(player).unwrap()))
+            } else {
+                spec.build(player).unwrap()
+            };
+            if !watch {
+                return bot;
+            }
+            Box::new(SpectatorBot::new(
+                bot,
+                control.clone(),
                 receiver_mutex.clone(),
                 input_sender.clone(),
             ))
-        } else {
-            Box::new(GreedyBot::new(Player::White))
-        };
-
-        let bot_b: Box<dyn Bot> = if player_ct > 1 {
-            Box::new(HumanBot::new(Player::Black, receiver_mutex, input_sender))
-        } else {
-            Box::new(MCTSBot::new(Player::Black, 100))
         };
+        let bot_w = build(&white, Player::White);
+        let bot_b = build(&black, Player::Black);
 
         let mut names = FxHashMap::default();
         names.insert(Player::Black, bot_b.get_name());
         names.insert(Player::White, bot_w.get_name());
 
-        Match::play(vec![bot_w, bot_b], record)
-            .unwrap()
-            .print(names);
+        let bots = vec![bot_w, bot_b];
+        let result = if has_human || watch {
+            Match::play_logged(bots, record, &input_sender)
+        } else {
+            Match::play(bots, record)
+        };
+        result.unwrap().print(names);
     });
 
-    if player_ct > 0 {
+    if has_human || watch {
         let options = eframe::NativeOptions {
             initial_window_size: Some(egui::vec2(1600.0, 900.0)),
             ..Default::default()
@@ -131,13 +424,56 @@ fn demo_p(player_ct: u8, record: Option<PathBuf>) {
         eframe::run_native(
             "Crabcassonne",
             options,
-            Box::new(|_cc| Box::new(MyApp::create(sender, input_receiver))),
+            Box::new(move |_cc| {
+                let app = MyApp::create(sender, input_receiver);
+                Box::new(match watch {
+                    true => app.with_spectator(delay, watched_seats),
+                    false => app,
+                })
+            }),
         )
         .unwrap();
     }
     handle.join().unwrap();
 }
 
+fn analyze(input: PathBuf, move_number: Option<usize>, spec: BotSpec, blunder: f64) {
+    if spec.is_human() {
+        println!("positions can only be analyzed by bots");
+        return;
+    }
+    let replay = Replay::from_path(input).unwrap();
+    let n = move_number
+        .unwrap_or(replay.moves.len())
+        .min(replay.moves.len());
+    let state = replay.state_at(n).unwrap();
+    let player = state.get_player();
+    let mut bot = spec.build(player.clone()).unwrap();
+    let Some(analysis) = bot.analyze(&state) else {
+        println!("{} has no analysis", bot.get_name());
+        return;
+    };
+
+    let played: Option<MoveRequest> = replay.moves.get(n).map(|one_move| one_move.into());
+    println!(
+        "move {n}, {player} to place, {} legal moves, analysis by {}",
+        state.get_legal_moves().len(),
+        bot.get_name()
+    );
+    analysis.print(usize::MAX, played.as_ref());
+
+    let Some(played) = played else {
+        return;
+    };
+    match analysis.shortfall(&played) {
+        Some(loss) if loss >= blunder => {
+            println!("played move (*) is a blunder, {loss:.2} below the best")
+        }
+        Some(loss) => println!("played move (*) is {loss:.2} below the best"),
+        None => println!("played move was not among the analyzed moves"),
+    }
+}
+
 #[derive(Default)]
 struct AggStats {
     pub white_win: u32,
@@ -158,37 +494,20 @@ impl AggStats {
     }
 }
 
-fn demo_threaded(n: u32, is_fast: bool) {
-    let get_fast_white = || -> Box<dyn Bot> { Box::new(RandomBot::new(Player::White)) };
-    let get_fast_black = || -> Box<dyn Bot> { Box::new(RandomBot::new(Player::Black)) };
-    let get_white = || -> Box<dyn Bot> { Box::new(ShallowBot::new(Player::White, 100)) };
-    let get_black = || -> Box<dyn Bot> { Box::new(MCTSBot::new(Player::Black, 100)) };
+fn demo_threaded(n: u32, white: BotSpec, black: BotSpec, log: &GameLog) {
+    if let Err(err) = white.require_headless().and(black.require_headless()) {
+        println!("{err}");
+        return;
+    }
 
     let mut stats = AggStats::default();
-    let bar = ProgressBar::new(n as u64);
-
-    let progress_style = ProgressStyle::with_template(
-        "[{elapsed}/{duration}] {bar:40.green/white} {pos:>7}/{len:7} {msg}",
-    )
-    .unwrap();
-    bar.set_style(progress_style);
-    bar.inc(0);
+    let bar = progress_bar(n as u64);
 
     let game_results: Vec<AggStats> = (0..n)
         .into_par_iter()
         .map(|_| {
             let mut stats = AggStats::default();
-            let bot_w = if is_fast {
-                get_fast_white()
-            } else {
-                get_white()
-            };
-            let bot_b = if is_fast {
-                get_fast_black()
-            } else {
-                get_black()
-            };
-            let result = Match::play(vec![bot_w, bot_b], None).unwrap();
+            let result = play_game(&white, &black, rand::random(), log).unwrap();
 
             stats.white_advantage += *result.player_scores.get(&Player::White).unwrap() as i32;
             stats.white_advantage -= *result.player_scores.get(&Player::Black).unwrap() as i32;
@@ -212,11 +531,32 @@ fn demo_threaded(n: u32, is_fast: bool) {
     let n = stats.get_n();
     println!(
         "White ({}) winrate {:.2}, Draw-rate {:.2}, Black ({}) winrate {:.2}\nwhite avg score advantage = {:.1} (n = {n})",
-        get_white().get_name(),
+        white.build(Player::White).unwrap().get_name(),
         stats.white_win as f64 / n as f64,
         stats.draw as f64 / n as f64,
-        get_black().get_name(),
+        black.build(Player::Black).unwrap().get_name(),
         stats.black_win as f64 / n as f64,
         stats.white_advantage as f64 / n as f64,
     );
 }
+
+fn tournament(
+    bots: Vec<BotSpec>,
+    num_games: u32,
+    csv: Option<PathBuf>,
+    json: Option<PathBuf>,
+    log: GameLogArgs,
+) {
+    if let Err(err) = bots.iter().try_for_each(BotSpec::require_headless) {
+        println!("{err}");
+        return;
+    }
+    let table = round_robin(&bots, num_games, &GameLog::new(log).unwrap());
+    table.print();
+    if let Some(path) = csv {
+        std::fs::write(path, table.to_csv()).unwrap();
+    }
+    if let Some(path) = json {
+        std::fs::write(path, serde_json::to_string_pretty(&table).unwrap()).unwrap();
+    }
+}
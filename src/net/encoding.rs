@@ -0,0 +1,183 @@
+use once_cell::sync::Lazy;
+
+use crate::{
+    board::{BoardData, ConcreteBoard, Coordinate},
+    bots::bot::MoveRequest,
+    referee::RefereeState,
+    tile::{MiniTile, TileClickTarget, TileData, ROTATIONS},
+    tilebag::{LegalTileBag, TileBag},
+};
+
+// side of the square of cells fed to the network, centered on the board
+pub const WINDOW: usize = 13;
+const CELL_FEATURES: usize = 16;
+const TILE_KINDS: usize = 24;
+const GLOBAL_FEATURES: usize = 5 + 2 * TILE_KINDS;
+pub const INPUT_SIZE: usize = WINDOW * WINDOW * CELL_FEATURES + GLOBAL_FEATURES;
+
+const MEEPLE_SLOTS: [Option<TileClickTarget>; 6] = [
+    None,
+    Some(TileClickTarget::Top),
+    Some(TileClickTarget::Left),
+    Some(TileClickTarget::Center),
+    Some(TileClickTarget::Right),
+    Some(TileClickTarget::Bottom),
+];
+// one policy output per (cell, rotation, meeple slot)
+pub const POLICY_SIZE: usize = WINDOW * WINDOW * ROTATIONS.len() * MEEPLE_SLOTS.len();
+
+static TILE_KIND_LIST: Lazy<Vec<TileData>> = Lazy::new(|| {
+    let kinds: Vec<TileData> = LegalTileBag::default()
+        .get_type_counts()
+        .into_iter()
+        .map(|(tile, _)| tile)
+        .collect();
+    // the input size is fixed at compile time, so it has to agree with the bag
+    assert_eq!(kinds.len(), TILE_KINDS);
+    kinds
+});
+
+fn tile_kind(tile: &TileData) -> Option<usize> {
+    TILE_KIND_LIST
+        .iter()
+        .position(|kind| kind.is_same_type(tile))
+}
+
+// Encodes positions from the point of view of the player to move
+pub struct Encoder {
+    origin: Coordinate,
+}
+
+impl Encoder {
+    pub fn new(board: &ConcreteBoard) -> Self {
+        let ((min_row, max_row), (min_col, max_col)) = board.boundaries();
+        let half = (WINDOW / 2) as i8;
+        Self {
+            origin: (
+                ((min_row as i16 + max_row as i16) / 2) as i8 - half,
+                ((min_col as i16 + max_col as i16) / 2) as i8 - half,
+            ),
+        }
+    }
+
+    fn cell(&self, coord: &Coordinate) -> Option<usize> {
+        let row = coord.0 as i16 - self.origin.0 as i16;
+        let col = coord.1 as i16 - self.origin.1 as i16;
+        let range = 0..WINDOW as i16;
+        if range.contains(&row) && range.contains(&col) {
+            Some(row as usize * WINDOW + col as usize)
+        } else {
+            None
+        }
+    }
+
+    pub fn move_index(&self, request: &MoveRequest) -> Option<usize> {
+        let cell = self.cell(&request.coord)?;
+        let rotation = ROTATIONS.iter().position(|r| r == &request.rotation)?;
+        let meeple = MEEPLE_SLOTS.iter().position(|m| m == &request.meeple)?;
+        Some((cell * ROTATIONS.len() + rotation) * MEEPLE_SLOTS.len() + meeple)
+    }
+
+    pub fn encode(&self, state: &RefereeState) -> Vec<f32> {
+        let mut out = vec![0.0; INPUT_SIZE];
+        let own_player = state.get_player();
+
+        for coord in state.board.tiles_present() {
+            let (Some(cell), Some(tile)) = (self.cell(&coord), state.board.at(&coord)) else {
+                continue;
+            };
+            let features = &mut out[cell * CELL_FEATURES..(cell + 1) * CELL_FEATURES];
+            features[0] = 1.0;
+            for (i, direction) in [
+                TileClickTarget::Top,
+                TileClickTarget::Left,
+                TileClickTarget::Right,
+                TileClickTarget::Bottom,
+            ]
+            .iter()
+            .enumerate()
+            {
+                match tile.at(direction) {
+                    MiniTile::City => features[1 + 2 * i] = 1.0,
+                    MiniTile::Road => features[2 + 2 * i] = 1.0,
+                    _ => {}
+                }
+            }
+            for center in [Some(&tile.center), tile.secondary_center.as_ref()]
+                .into_iter()
+                .flatten()
+            {
+                match center {
+                    MiniTile::City => features[9] = 1.0,
+                    MiniTile::Road => features[10] = 1.0,
+                    MiniTile::Monastery => features[11] = 1.0,
+                    MiniTile::Junction => features[12] = 1.0,
+                    MiniTile::Grass => {}
+                }
+            }
+            if tile.has_emblem {
+                features[13] = 1.0;
+            }
+            for player in tile.get_meeple_locations().values() {
+                if player == &own_player {
+                    features[14] = 1.0;
+                } else {
+                    features[15] = 1.0;
+                }
+            }
+        }
+
+        let globals = &mut out[WINDOW * WINDOW * CELL_FEATURES..];
+        for player in &state.turn_order {
+            let score = *state.player_scores.get(player).unwrap_or(&0) as f32 / 100.0;
+            let meeples = *state.player_meeples.get(player).unwrap_or(&0) as f32 / 7.0;
+            let offset = if player == &own_player { 0 } else { 1 };
+            globals[offset] += score;
+            globals[2 + offset] += meeples;
+        }
+        globals[4] = state.tilebag.count_remaining() as f32 / 72.0;
+        for (tile, count) in state.tilebag.get_type_counts() {
+            if let Some(kind) = tile_kind(&tile) {
+                globals[5 + kind] = count as f32 / 10.0;
+            }
+        }
+        if let Some(kind) = state.tilebag.peek().ok().and_then(tile_kind) {
+            globals[5 + TILE_KINDS + kind] = 1.0;
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::referee::Player;
+
+    #[test]
+    fn legal_moves_have_distinct_indices() {
+        let mut state = RefereeState::from_players(
+            vec![Player::White, Player::Black],
+            LegalTileBag::default().into(),
+        );
+        state
+            .process_move(state.get_legal_moves()[0].clone())
+            .unwrap();
+        let encoder = Encoder::new(&state.board);
+        assert_eq!(encoder.encode(&state).len(), INPUT_SIZE);
+
+        let moves = state.get_legal_moves();
+        let mut indices: Vec<usize> = moves
+            .iter()
+            .map(|request| encoder.move_index(request).unwrap())
+            .collect();
+        assert!(indices.iter().all(|idx| *idx < POLICY_SIZE));
+        indices.sort();
+        indices.dedup();
+        assert_eq!(indices.len(), moves.len());
+    }
+
+    #[test]
+    fn tile_kinds_match_bag() {
+        assert_eq!(TILE_KIND_LIST.len(), TILE_KINDS);
+    }
+}
@@ -0,0 +1,3 @@
+pub mod encoding;
+pub mod network;
+pub mod self_play;
@@ -0,0 +1,217 @@
+use std::{
+    fs::File,
+    io::{Read, Write},
+    path::PathBuf,
+};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::arena::MessageResult;
+
+use super::encoding::{INPUT_SIZE, POLICY_SIZE};
+
+// One training position: encoded input, visit distribution over policy indices and
+// the eventual outcome for the player to move
+pub struct Sample {
+    pub input: Vec<f32>,
+    pub policy: Vec<(usize, f32)>,
+    pub value: f32,
+}
+
+// Single hidden layer with a policy head and a tanh value head, small enough to run on CPU
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Network {
+    hidden: usize,
+    // INPUT_SIZE rows of `hidden` weights, the input is sparse so it is walked by row
+    w_hidden: Vec<f32>,
+    b_hidden: Vec<f32>,
+    // POLICY_SIZE rows of `hidden` weights
+    w_policy: Vec<f32>,
+    b_policy: Vec<f32>,
+    w_value: Vec<f32>,
+    b_value: f32,
+}
+
+fn init_weights(len: usize, fan_in: usize, fan_out: usize) -> Vec<f32> {
+    let limit = (6.0 / (fan_in + fan_out) as f32).sqrt();
+    let mut rng = rand::thread_rng();
+    (0..len).map(|_| rng.gen_range(-limit..limit)).collect()
+}
+
+fn dot(l: &[f32], r: &[f32]) -> f32 {
+    l.iter().zip(r).map(|(a, b)| a * b).sum()
+}
+
+impl Network {
+    pub fn new(hidden: usize) -> Self {
+        Self {
+            hidden,
+            w_hidden: init_weights(INPUT_SIZE * hidden, INPUT_SIZE, hidden),
+            b_hidden: vec![0.0; hidden],
+            w_policy: init_weights(POLICY_SIZE * hidden, hidden, POLICY_SIZE),
+            b_policy: vec![0.0; POLICY_SIZE],
+            w_value: init_weights(hidden, hidden, 1),
+            b_value: 0.0,
+        }
+    }
+
+    pub fn from_path(input: PathBuf) -> MessageResult<Self> {
+        let file = File::open(input).or(Err("failed to open file"))?;
+        let mut buf_reader = std::io::BufReader::new(file);
+        let mut json_string = String::new();
+        buf_reader
+            .read_to_string(&mut json_string)
+            .or(Err("failed to read file"))?;
+        let out: Self = serde_json::from_str(&json_string).or(Err("failed to deserialize file"))?;
+        if out.w_hidden.len() != INPUT_SIZE * out.hidden
+            || out.w_policy.len() != POLICY_SIZE * out.hidden
+        {
+            return Err("network does not match the current encoding");
+        }
+        Ok(out)
+    }
+
+    pub fn save(&self, path: PathBuf) -> MessageResult<()> {
+        let file = File::create(path).or(Err("Failed to create network file"))?;
+        let mut file_writer = std::io::BufWriter::new(file);
+        let json_string = serde_json::to_string(self).or(Err("Failed to serialize network"))?;
+        file_writer
+            .write_all(json_string.as_bytes())
+            .or(Err("Failed to write to file"))
+    }
+
+    // pre-activations of the hidden layer
+    fn hidden_layer(&self, input: &[f32]) -> Vec<f32> {
+        let mut out = self.b_hidden.clone();
+        for (i, x) in input.iter().enumerate() {
+            if *x == 0.0 {
+                continue;
+            }
+            let row = &self.w_hidden[i * self.hidden..(i + 1) * self.hidden];
+            for (h, w) in out.iter_mut().zip(row) {
+                *h += x * w;
+            }
+        }
+        out
+    }
+
+    fn activate(pre: &[f32]) -> Vec<f32> {
+        pre.iter().map(|x| x.max(0.0)).collect()
+    }
+
+    fn policy_logits(&self, hidden: &[f32]) -> Vec<f32> {
+        self.w_policy
+            .chunks(self.hidden)
+            .zip(&self.b_policy)
+            .map(|(row, b)| b + dot(row, hidden))
+            .collect()
+    }
+
+    fn value_head(&self, hidden: &[f32]) -> f32 {
+        (self.b_value + dot(&self.w_value, hidden)).tanh()
+    }
+
+    // unnormalized policy logits, indexed like `Encoder::move_index`
+    pub fn policy(&self, input: &[f32]) -> Vec<f32> {
+        let hidden = Self::activate(&self.hidden_layer(input));
+        self.policy_logits(&hidden)
+    }
+
+    // expected outcome in [-1, 1] for the player to move
+    pub fn value(&self, input: &[f32]) -> f32 {
+        let hidden = Self::activate(&self.hidden_layer(input));
+        self.value_head(&hidden)
+    }
+
+    // one SGD step per sample, returns the mean of policy cross entropy plus value squared error
+    pub fn train(&mut self, samples: &[Sample], learning_rate: f32) -> f32 {
+        let mut total_loss = 0.0;
+        for sample in samples {
+            let pre = self.hidden_layer(&sample.input);
+            let hidden = Self::activate(&pre);
+            let logits = self.policy_logits(&hidden);
+            let value = self.value_head(&hidden);
+
+            let max = logits.iter().cloned().fold(f32::MIN, f32::max);
+            let exps: Vec<f32> = logits.iter().map(|l| (l - max).exp()).collect();
+            let sum: f32 = exps.iter().sum();
+            let mut grad_logits: Vec<f32> = exps.iter().map(|e| e / sum).collect();
+            for (idx, target) in &sample.policy {
+                total_loss -= target * (grad_logits[*idx].max(1e-9)).ln();
+                grad_logits[*idx] -= target;
+            }
+            let error = value - sample.value;
+            total_loss += error * error;
+            let grad_value = 2.0 * error * (1.0 - value * value);
+
+            let mut grad_hidden: Vec<f32> = self.w_value.iter().map(|w| w * grad_value).collect();
+            for ((row, b), g) in self
+                .w_policy
+                .chunks_mut(self.hidden)
+                .zip(self.b_policy.iter_mut())
+                .zip(&grad_logits)
+            {
+                for ((w, h), gh) in row.iter_mut().zip(&hidden).zip(grad_hidden.iter_mut()) {
+                    *gh += *w * g;
+                    *w -= learning_rate * g * h;
+                }
+                *b -= learning_rate * g;
+            }
+            for (w, h) in self.w_value.iter_mut().zip(&hidden) {
+                *w -= learning_rate * grad_value * h;
+            }
+            self.b_value -= learning_rate * grad_value;
+
+            for (gh, p) in grad_hidden.iter_mut().zip(&pre) {
+                if *p <= 0.0 {
+                    *gh = 0.0;
+                }
+            }
+            for (i, x) in sample.input.iter().enumerate() {
+                if *x == 0.0 {
+                    continue;
+                }
+                let row = &mut self.w_hidden[i * self.hidden..(i + 1) * self.hidden];
+                for (w, gh) in row.iter_mut().zip(&grad_hidden) {
+                    *w -= learning_rate * gh * x;
+                }
+            }
+            for (b, gh) in self.b_hidden.iter_mut().zip(&grad_hidden) {
+                *b -= learning_rate * gh;
+            }
+        }
+        total_loss / samples.len().max(1) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn training_fits_single_sample() {
+        let mut net = Network::new(16);
+        let mut input = vec![0.0; INPUT_SIZE];
+        input[3] = 1.0;
+        input[INPUT_SIZE - 1] = 0.5;
+        let sample = Sample {
+            input: input.clone(),
+            policy: vec![(7, 1.0)],
+            value: 0.5,
+        };
+
+        let first_loss = net.train(std::slice::from_ref(&sample), 0.05);
+        for _ in 0..200 {
+            net.train(std::slice::from_ref(&sample), 0.05);
+        }
+        assert!(net.train(std::slice::from_ref(&sample), 0.05) < first_loss);
+        assert!((net.value(&input) - 0.5).abs() < 0.1);
+
+        let logits = net.policy(&input);
+        let best = (0..POLICY_SIZE)
+            .max_by(|l, r| logits[*l].partial_cmp(&logits[*r]).unwrap())
+            .unwrap();
+        assert_eq!(best, 7);
+    }
+}
@@ -0,0 +1,71 @@
+use std::sync::{Arc, Mutex};
+
+use rand::seq::SliceRandom;
+use rayon::prelude::*;
+
+use crate::{
+    arena::Match,
+    bots::{bot::Bot, mcts_bot::score_margin, net_bot::NetBot},
+    referee::Player,
+    tilebag::LegalTileBag,
+};
+
+use super::network::{Network, Sample};
+
+// A position seen during self-play, its value is only known once the game ends
+pub struct PendingSample {
+    pub input: Vec<f32>,
+    pub policy: Vec<(usize, f32)>,
+    pub player: Player,
+}
+
+pub type SampleRecorder = Arc<Mutex<Vec<PendingSample>>>;
+
+// squashes a final score margin into the value head's [-1, 1] range
+pub fn outcome_value(margin: f64) -> f32 {
+    (margin / 20.0).tanh() as f32
+}
+
+pub fn self_play_game(net: &Arc<Network>, iterations: u32) -> Vec<Sample> {
+    let recorder: SampleRecorder = Arc::new(Mutex::new(vec![]));
+    let bots: Vec<Box<dyn Bot>> = [Player::White, Player::Black]
+        .into_iter()
+        .map(|player| -> Box<dyn Bot> {
+            Box::new(NetBot::recording(
+                player,
+                iterations,
+                net.clone(),
+                recorder.clone(),
+            ))
+        })
+        .collect();
+    let result =
+        Match::play_custom(bots, LegalTileBag::default().into(), None, None, None).unwrap();
+
+    let pending = std::mem::take(&mut *recorder.lock().unwrap());
+    pending
+        .into_iter()
+        .map(|sample| Sample {
+            value: outcome_value(score_margin(&result.player_scores, &sample.player)),
+            input: sample.input,
+            policy: sample.policy,
+        })
+        .collect()
+}
+
+pub fn generate(net: &Arc<Network>, games: u32, iterations: u32) -> Vec<Sample> {
+    (0..games)
+        .into_par_iter()
+        .flat_map_iter(|_| self_play_game(net, iterations))
+        .collect()
+}
+
+// shuffled SGD passes over the samples, returns the loss of the last epoch
+pub fn train(net: &mut Network, samples: &mut [Sample], epochs: u32, learning_rate: f32) -> f32 {
+    let mut loss = 0.0;
+    for _ in 0..epochs {
+        samples.shuffle(&mut rand::thread_rng());
+        loss = net.train(samples, learning_rate);
+    }
+    loss
+}
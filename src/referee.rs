@@ -1,16 +1,19 @@
-use std::fmt;
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+};
 
 use egui::Color32;
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHasher};
 use serde::{Deserialize, Serialize};
 
 use crate::{
     arena::MessageResult,
-    board::{BoardData, OverlaidBoard},
+    board::{mix, BoardData, OverlaidBoard, ScoringData},
     board::{ConcreteBoard, Coordinate},
     bots::bot::MoveRequest,
     render::RenderState,
-    tile::{Rotation, TileClickTarget, TileData},
+    tile::{Rotation, TileClickTarget, TileData, ROTATIONS},
     tilebag::{LegalTileBag, TileBag, TileBagEnum},
 };
 
@@ -90,6 +93,36 @@ impl RefereeState {
             ..Default::default()
         }
     }
+    // identifies the position independent of draw order: board, scores, meeples, whose
+    // turn it is and the tiles left in the bag, though not which of them comes next
+    pub fn position_hash(&self) -> u64 {
+        let mut hasher = FxHasher::default();
+        self.board.zobrist().hash(&mut hasher);
+        self.turn_idx.hash(&mut hasher);
+        for player in &self.turn_order {
+            self.player_scores.get(player).hash(&mut hasher);
+            self.player_meeples.get(player).hash(&mut hasher);
+        }
+        // the bag as a multiset, summed so order doesn't matter but duplicates do
+        let bag = self.tilebag.get_data().iter().fold(0u64, |sum, tile| {
+            let mut tile_hasher = FxHasher::default();
+            tile.hash_type(&mut tile_hasher);
+            sum.wrapping_add(mix(tile_hasher.finish()))
+        });
+        bag.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    // `position_hash` plus the tile about to be placed
+    pub fn decision_hash(&self) -> u64 {
+        let mut hasher = FxHasher::default();
+        self.position_hash().hash(&mut hasher);
+        if let Ok(tile) = self.tilebag.peek() {
+            tile.hash_type(&mut hasher);
+        }
+        hasher.finish()
+    }
+
     pub fn clone_into_mid_move(
         &self,
         preview_placed: Option<(Coordinate, Rotation)>,
@@ -98,13 +131,38 @@ impl RefereeState {
         let player = self.get_player();
         let mut board = self.board.clone();
         let preview_tile = self.tilebag.peek().ok().cloned();
-        if let Some((coord, rotation)) = preview_placed {
+        if let Some((coord, rotation)) = preview_placed.clone() {
             if let Some(tile) = &preview_tile {
                 let mut tile = tile.clone();
                 tile.rotation = rotation;
                 board.set(coord, tile);
             }
         }
+        let mut legal_placements = FxHashMap::default();
+        let mut meeple_targets = None;
+        match (&preview_placed, is_placing_meeple) {
+            (Some((coord, _)), true) => {
+                let has_meeples = self.player_meeples.get(&player).unwrap_or(&0) > &0;
+                let targets = [
+                    TileClickTarget::Top,
+                    TileClickTarget::Left,
+                    TileClickTarget::Center,
+                    TileClickTarget::Right,
+                    TileClickTarget::Bottom,
+                ]
+                .into_iter()
+                .filter(|target| {
+                    has_meeples
+                        && board
+                            .as_overlay()
+                            .is_legal_meeple(coord, target.clone())
+                            .is_ok()
+                })
+                .collect();
+                meeple_targets = Some((*coord, targets));
+            }
+            _ => legal_placements = self.legal_placements(),
+        }
         RenderState {
             preview_tile,
             board,
@@ -113,9 +171,38 @@ impl RefereeState {
             current_player: player,
             player_scores: self.player_scores.clone(),
             player_meeples: self.player_meeples.clone(),
+            hints: vec![],
+            legal_placements,
+            meeple_targets,
+            last_placed: None,
+            bag: self.remaining_tiles(),
         }
     }
 
+    // empty cells the next tile fits in, with the rotations that fit
+    pub fn legal_placements(&self) -> FxHashMap<Coordinate, Vec<Rotation>> {
+        let Ok(tile) = self.tilebag.peek() else {
+            return FxHashMap::default();
+        };
+        let board = self.board_overlay();
+        board
+            .get_legal_tiles()
+            .into_iter()
+            .filter_map(|coord| {
+                let rotations: Vec<Rotation> = ROTATIONS
+                    .iter()
+                    .filter(|rotation| {
+                        let mut tile = tile.clone();
+                        tile.rotation = (*rotation).clone();
+                        board.is_features_match(&coord, &tile)
+                    })
+                    .cloned()
+                    .collect();
+                (!rotations.is_empty()).then_some((coord, rotations))
+            })
+            .collect()
+    }
+
     pub fn clone_into(&self) -> RenderState {
         let player = self.get_player();
         RenderState {
@@ -126,9 +213,30 @@ impl RefereeState {
             current_player: player,
             player_scores: self.player_scores.clone(),
             player_meeples: self.player_meeples.clone(),
+            hints: vec![],
+            legal_placements: FxHashMap::default(),
+            meeple_targets: None,
+            last_placed: None,
+            bag: self.remaining_tiles(),
         }
     }
-    fn board_overlay(&self) -> OverlaidBoard {
+    // tiles left to draw by kind, not counting the one about to be placed
+    pub fn remaining_tiles(&self) -> Vec<(TileData, u32)> {
+        let mut counts = self.tilebag.get_type_counts();
+        if let Ok(current) = self.tilebag.peek() {
+            if let Some(idx) = counts
+                .iter()
+                .position(|(kind, _)| kind.is_same_type(current))
+            {
+                counts[idx].1 -= 1;
+                if counts[idx].1 == 0 {
+                    counts.remove(idx);
+                }
+            }
+        }
+        counts
+    }
+    fn board_overlay(&self) -> OverlaidBoard<'_> {
         self.board.as_overlay()
     }
     fn score_placement(&mut self) {
@@ -215,15 +323,37 @@ impl RefereeState {
         self.board_overlay()
             .is_legal_meeple(&coord, location.clone())?;
 
-        let tile = self.board.at_mut(&coord);
-        if let Some(tile) = tile {
-            tile.place_meeple(&location, &player)?;
-            self.player_meeples.insert(player, meeples_remaining - 1);
-            self.progress_phase(None);
-            Ok(())
-        } else {
-            Err("placing meeple on non existant tile")
+        self.board.place_meeple(&coord, &location, &player)?;
+        self.player_meeples.insert(player, meeples_remaining - 1);
+        self.progress_phase(None);
+        Ok(())
+    }
+    // features `request` would complete that score for someone
+    pub fn completed_features(&self, request: &MoveRequest) -> MessageResult<Vec<ScoringData>> {
+        let mut tile = self.tilebag.peek()?.clone();
+        tile.rotation = request.rotation.clone();
+        if let Some(target) = &request.meeple {
+            tile.place_meeple(target, &self.get_player())?;
+        }
+        Ok(self
+            .board_overlay()
+            .get_feature_score_data(&request.coord, &tile)
+            .into_iter()
+            .filter(|data| data.completed && data.points > 0 && !data.scoring_players.is_empty())
+            .collect())
+    }
+    // banked score plus whatever the meeples still on the board are worth
+    pub fn get_final_scores(&self) -> FxHashMap<Player, u32> {
+        let mut scores = self.board_overlay().get_standing_points();
+        for player in &self.turn_order {
+            let delta = self.player_scores.get(player).unwrap_or(&0);
+            if let Some(score) = scores.get_mut(player) {
+                *score += *delta
+            } else {
+                scores.insert(player.clone(), *delta);
+            }
         }
+        scores
     }
     pub fn get_next_player(&self) -> Player {
         self.turn_order[(self.turn_idx + 1) % self.turn_order.len()].clone()
@@ -1,14 +1,23 @@
-use std::sync::mpsc::{Receiver, Sender};
+use std::{
+    sync::mpsc::{Receiver, Sender},
+    time::Duration,
+};
 
 use eframe::egui;
-use egui::{vec2, Id};
-use rustc_hash::FxHashMap;
+use egui::{
+    plot::{Legend, Line, LineStyle, Plot, PlotPoints, VLine},
+    pos2, vec2, Id, Pos2, Rect, Vec2,
+};
+use itertools::Itertools;
+use rustc_hash::{FxHashMap, FxHashSet};
 
 use crate::{
-    board::{BoardData, ConcreteBoard, Coordinate},
+    board::{BoardData, ConcreteBoard, Coordinate, FeatureResult},
+    bots::bot::MoveHint,
     referee::Player,
-    render_tile,
-    tile::{Rotation, TileClickTarget, TileData},
+    render_tile::{self, CellHints},
+    tile::{MiniTile, Rotation, TileClickTarget, TileData},
+    tile_art,
 };
 
 #[derive(Clone)]
@@ -23,10 +32,28 @@ pub enum InteractionMessage {
     Print(String),
     Click(ClickMessage),
     CancelMeeple,
-    NextFrame,
-    PreviousFrame,
-    FirstFrame,
-    LastFrame,
+    /// Jump a replay to the frame after this many moves, less one
+    ShowFrame(usize),
+    Spectate(SpectateCommand),
+}
+
+// Controls of a game between bots being watched
+#[derive(Clone)]
+pub enum SpectateCommand {
+    TogglePause,
+    /// Let the bot to move play while paused
+    Step,
+    Delay(Duration),
+    /// Hand the seat to the human at the window
+    TakeOver(Player),
+}
+
+// What the window knows of a watched game
+struct Spectator {
+    paused: bool,
+    delay_ms: u64,
+    /// Seats still played by bots
+    seats: Vec<Player>,
 }
 
 #[derive(Clone)]
@@ -38,20 +65,139 @@ pub struct RenderState {
     pub current_player: Player,
     pub player_meeples: FxHashMap<Player, u8>,
     pub player_scores: FxHashMap<Player, u32>,
+    pub hints: Vec<MoveHint>,
+    /// Empty cells the preview tile fits in, with the rotations that fit
+    pub legal_placements: FxHashMap<Coordinate, Vec<Rotation>>,
+    /// While placing a meeple, the new tile and where on it a meeple may go
+    pub meeple_targets: Option<(Coordinate, Vec<TileClickTarget>)>,
+    /// The tile placed by the move that led here, highlighted in replays
+    pub last_placed: Option<Coordinate>,
+    /// Tile kinds left to draw with counts, without the one being placed
+    pub bag: Vec<(TileData, u32)>,
+}
+
+// One move in a replay's move list
+#[derive(Clone)]
+pub struct TimelineEntry {
+    pub player: Player,
+    /// `TileData::code` of the tile placed
+    pub tile: String,
+    pub coord: Coordinate,
+    pub rotation: Rotation,
+    pub meeple: Option<TileClickTarget>,
+    /// Points banked by each player on this move
+    pub points: Vec<(Player, u32)>,
+}
+
+// Per player totals after one move, a point on the score chart
+#[derive(Clone, Default)]
+pub struct TurnStats {
+    pub scores: FxHashMap<Player, u32>,
+    pub standing: FxHashMap<Player, u32>,
+    pub meeples: FxHashMap<Player, u8>,
+}
+
+impl TurnStats {
+    pub fn new(state: &RenderState) -> Self {
+        Self {
+            scores: state.player_scores.clone(),
+            standing: state.board.as_overlay().get_standing_points(),
+            meeples: state.player_meeples.clone(),
+        }
+    }
+}
+
+// Replay controls, the frame shown is `frame` and is driven from here
+struct Timeline {
+    entries: Vec<TimelineEntry>,
+    frame: usize,
+    playing: bool,
+    /// Moves per second while playing
+    speed: f32,
+    last_step: f64,
+}
+
+// One player's line in the end of game results
+#[derive(Clone)]
+pub struct FinalScore {
+    pub player: Player,
+    pub name: String,
+    /// Points from features completed during the game
+    pub banked: u32,
+    /// Points from incomplete features scored at the end
+    pub endgame: u32,
+}
+
+impl FinalScore {
+    pub fn total(&self) -> u32 {
+        self.banked + self.endgame
+    }
 }
 
+#[allow(clippy::large_enum_variant)]
 pub enum RenderMessage {
     RefereeSync(RenderState),
+    /// A line for the message log: rejected moves, scoring, discards
+    Log(String),
+    GameOver(Vec<FinalScore>),
+    /// Sent once by the replay viewer, one entry per frame
+    Timeline(Vec<TimelineEntry>),
+    /// Totals after the next move, for the score chart
+    Stats(TurnStats),
 }
 
 pub struct MyApp {
-    zoom: usize,
+    /// Side of a cell in points
+    zoom: f32,
+    /// Offset of cell (0, 0) from the middle of the board view
+    pan: Vec2,
+    fit_requested: bool,
     render_state: Option<RenderState>,
+    log: Vec<String>,
+    results: Option<Vec<FinalScore>>,
+    show_results: bool,
+    /// Cell picked with the arrow keys, where Enter places the tile
+    cursor: Option<Coordinate>,
+    show_shortcuts: bool,
+    timeline: Option<Timeline>,
+    history: Vec<TurnStats>,
+    show_chart: bool,
+    show_bag: bool,
+    spectator: Option<Spectator>,
     pub output_channel: Sender<InteractionMessage>,
     pub input_channel: Receiver<RenderMessage>,
 }
 
 pub const TILE_CLICK_ID: &str = "subtile";
+const MIN_ZOOM: f32 = 20.0;
+const MAX_ZOOM: f32 = 240.0;
+// distance between cell centers relative to the cell size
+const CELL_PITCH: f32 = 1.125;
+const MINIMAP_SIZE: Vec2 = vec2(180.0, 140.0);
+// the (coord, target) of the placed sub-tile under the pointer, set while painting tiles
+pub const FEATURE_HOVER_ID: &str = "hovered_feature";
+
+// meeple targets in the order of the number keys that pick them
+pub const MEEPLE_KEYS: [(egui::Key, TileClickTarget); 5] = [
+    (egui::Key::Num1, TileClickTarget::Top),
+    (egui::Key::Num2, TileClickTarget::Left),
+    (egui::Key::Num3, TileClickTarget::Center),
+    (egui::Key::Num4, TileClickTarget::Right),
+    (egui::Key::Num5, TileClickTarget::Bottom),
+];
+
+const SHORTCUTS: [(&str, &str); 10] = [
+    ("R / Q", "rotate the tile right / left"),
+    ("Arrows", "move the cursor between cells the tile fits"),
+    ("Enter", "place the tile at the cursor"),
+    ("1-5", "place a meeple on the numbered spot"),
+    ("X", "skip placing a meeple"),
+    ("Left / Right", "previous / next frame of a replay"),
+    ("Home / End", "first / last frame of a replay"),
+    ("Space", "play or pause a replay or a watched game"),
+    ("Wheel / drag", "zoom / pan the board"),
+    ("H", "show or hide this list"),
+];
 
 impl MyApp {
     pub fn create(
@@ -59,30 +205,652 @@ impl MyApp {
         board_channel: Receiver<RenderMessage>,
     ) -> Self {
         Self {
-            zoom: 80,
+            zoom: 80.0,
+            pan: Vec2::ZERO,
+            fit_requested: true,
             render_state: None,
+            log: vec![],
+            results: None,
+            show_results: false,
+            cursor: None,
+            show_shortcuts: false,
+            timeline: None,
+            history: vec![],
+            show_chart: false,
+            show_bag: false,
+            spectator: None,
             output_channel,
             input_channel: board_channel,
         }
     }
+
+    // adds the controls for watching `seats` played by bots
+    pub fn with_spectator(self, delay: Duration, seats: Vec<Player>) -> Self {
+        Self {
+            spectator: Some(Spectator {
+                paused: false,
+                delay_ms: delay.as_millis() as u64,
+                seats,
+            }),
+            ..self
+        }
+    }
+
+    // changes the zoom keeping the board point at `anchor`, relative to the middle of
+    // the view, in place
+    fn zoom_around(&mut self, anchor: Vec2, zoom: f32) {
+        let zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+        self.pan = anchor - (anchor - self.pan) * (zoom / self.zoom);
+        self.zoom = zoom;
+    }
+
+    // the board as a canvas that pans and zooms, laying out only the cells in view
+    fn board_view(&mut self, ui: &mut egui::Ui) {
+        let Some(((min_row, max_row), (min_col, max_col))) = self
+            .render_state
+            .as_ref()
+            .map(|state| state.board.boundaries())
+        else {
+            return;
+        };
+        // cells on the board plus the ring of empty ones around it
+        let rows = (min_row as i32 - 1, max_row as i32 + 1);
+        let cols = (min_col as i32 - 1, max_col as i32 + 1);
+        let (canvas, response) = ui.allocate_exact_size(ui.available_size(), egui::Sense::drag());
+
+        if std::mem::take(&mut self.fit_requested) {
+            let pitch = f32::min(
+                canvas.width() / (cols.1 - cols.0 + 1) as f32,
+                canvas.height() / (rows.1 - rows.0 + 1) as f32,
+            );
+            self.zoom = (pitch / CELL_PITCH).clamp(MIN_ZOOM, MAX_ZOOM);
+            let middle = vec2((cols.0 + cols.1) as f32, (rows.0 + rows.1) as f32) / 2.0;
+            self.pan = -middle * self.zoom * CELL_PITCH;
+        }
+        if response.dragged() {
+            self.pan += response.drag_delta();
+        }
+        if ui.rect_contains_pointer(canvas) {
+            let (scroll, pinch, pointer) =
+                ui.input(|i| (i.scroll_delta.y, i.zoom_delta(), i.pointer.hover_pos()));
+            let factor = pinch * (scroll * 0.002).exp();
+            if let (true, Some(pointer)) = (factor != 1.0, pointer) {
+                self.zoom_around(pointer - canvas.center(), self.zoom * factor);
+            }
+        }
+
+        let pitch = self.zoom * CELL_PITCH;
+        let origin = canvas.center() + self.pan;
+        // board space has cell (r, c) centered on (c, r)
+        let view = Rect::from_min_max(
+            ((canvas.min - origin) / pitch).to_pos2(),
+            ((canvas.max - origin) / pitch).to_pos2(),
+        );
+        let visible_rows = (
+            rows.0.max(view.top().floor() as i32),
+            rows.1.min(view.bottom().ceil() as i32),
+        );
+        let visible_cols = (
+            cols.0.max(view.left().floor() as i32),
+            cols.1.min(view.right().ceil() as i32),
+        );
+
+        if let Some(target) = self.minimap(ui.ctx(), canvas, rows, cols, view) {
+            self.pan = -target.to_vec2() * pitch;
+        }
+
+        let Some(state) = &self.render_state else {
+            return;
+        };
+        let check_placement = !state.legal_placements.is_empty();
+        let hovered = ui.ctx().data_mut(|map| {
+            let id = Id::new(FEATURE_HOVER_ID);
+            let hovered = map.get_temp::<(Coordinate, TileClickTarget)>(id);
+            map.remove::<(Coordinate, TileClickTarget)>(id);
+            hovered
+        });
+        let overlay = state.board.as_overlay();
+        let feature = hovered
+            .as_ref()
+            .and_then(|(coord, target)| overlay.get_feature_result(coord, target));
+        let feature_tiles: FxHashSet<Coordinate> = feature
+            .as_ref()
+            .map(|feature| feature.get_present_tiles().cloned().collect())
+            .unwrap_or_default();
+        if let (Some(feature), Some((coord, target))) = (&feature, &hovered) {
+            let completing = overlay.count_completing(coord, target, &state.bag);
+            let draws = state.turn_order.len() as u32;
+            egui::show_tooltip_at_pointer(ui.ctx(), Id::new("feature_tooltip"), |ui| {
+                feature_tooltip(ui, feature);
+                if !feature.completed {
+                    completion_odds(ui, completing, &state.bag, draws);
+                }
+            });
+        }
+
+        let mut board_ui = ui.child_ui(canvas, *ui.layout());
+        board_ui.set_clip_rect(canvas.intersect(ui.clip_rect()));
+        for r in visible_rows.0..=visible_rows.1 {
+            for c in visible_cols.0..=visible_cols.1 {
+                let coord = (r as i8, c as i8);
+                let cell = Rect::from_center_size(
+                    origin + vec2(c as f32, r as f32) * pitch,
+                    Vec2::splat(self.zoom),
+                );
+                let response = board_ui
+                    .push_id(coord, |ui| {
+                        ui.put(
+                            cell,
+                            render_tile::tile(
+                                self.zoom,
+                                state.board.at(&coord),
+                                coord,
+                                if state.is_placing_meeple {
+                                    &None
+                                } else {
+                                    &state.preview_tile
+                                },
+                                state.is_placing_meeple,
+                                state.current_player.clone(),
+                                CellHints {
+                                    check_placement,
+                                    legal_rotations: state
+                                        .legal_placements
+                                        .get(&coord)
+                                        .map(Vec::as_slice)
+                                        .unwrap_or(&[]),
+                                    meeple_targets: state
+                                        .meeple_targets
+                                        .as_ref()
+                                        .filter(|(at, _)| at == &coord)
+                                        .map(|(_, targets)| targets.as_slice()),
+                                    in_feature: feature_tiles.contains(&coord),
+                                    cursor: self.cursor == Some(coord),
+                                    last_placed: state.last_placed == Some(coord),
+                                },
+                            ),
+                        )
+                    })
+                    .inner;
+                if state.board.at(&coord).is_none() {
+                    paint_hints(&board_ui, &response.rect, coord, &state.hints);
+                }
+                response.ctx.data_mut(|map| {
+                    let subtile_id = Id::new(TILE_CLICK_ID);
+                    let maybe_val = map.get_temp::<InteractionMessage>(subtile_id);
+                    if let Some(val) = maybe_val {
+                        self.output_channel.send(val).unwrap();
+                    }
+                    map.remove::<InteractionMessage>(subtile_id);
+                })
+            }
+        }
+    }
+
+    // overview of the whole board in the corner of `canvas` with the part in `view`
+    // outlined, returns the board point clicked or dragged to
+    fn minimap(
+        &self,
+        ctx: &egui::Context,
+        canvas: Rect,
+        rows: (i32, i32),
+        cols: (i32, i32),
+        view: Rect,
+    ) -> Option<Pos2> {
+        let state = self.render_state.as_ref()?;
+        let rect = Rect::from_min_size(canvas.max - MINIMAP_SIZE - vec2(8.0, 8.0), MINIMAP_SIZE);
+        let board = Rect::from_min_max(
+            pos2(cols.0 as f32 - 0.5, rows.0 as f32 - 0.5),
+            pos2(cols.1 as f32 + 0.5, rows.1 as f32 + 0.5),
+        );
+        let scale = f32::min(rect.width() / board.width(), rect.height() / board.height());
+        let to_map = |p: Pos2| rect.center() + (p - board.center()) * scale;
+        // an area so the minimap takes clicks and drags over the tiles under it
+        egui::Area::new("minimap")
+            .fixed_pos(rect.min)
+            .show(ctx, |ui| {
+                let (_, response) =
+                    ui.allocate_exact_size(rect.size(), egui::Sense::click_and_drag());
+                let painter = ui.painter_at(rect);
+                painter.rect_filled(rect, 4.0, egui::Color32::from_black_alpha(180));
+                for coord in state.board.as_overlay().tiles_present() {
+                    let Some(tile) = state.board.at(&coord) else {
+                        continue;
+                    };
+                    let center = to_map(pos2(coord.1 as f32, coord.0 as f32));
+                    painter.rect_filled(
+                        Rect::from_center_size(center, Vec2::splat(scale.max(1.0))),
+                        0.0,
+                        minimap_color(tile),
+                    );
+                }
+                painter.rect_stroke(
+                    Rect::from_min_max(to_map(view.min), to_map(view.max)).intersect(rect),
+                    0.0,
+                    egui::Stroke::new(1.5, egui::Color32::GOLD),
+                );
+                let pointer = response.interact_pointer_pos()?;
+                (response.clicked() || response.dragged())
+                    .then(|| board.center() + (pointer - rect.center()) / scale)
+            })
+            .inner
+    }
+
+    fn send_click(&self, coord: Coordinate, location: TileClickTarget, rotation: Rotation) {
+        self.output_channel
+            .send(InteractionMessage::Click(ClickMessage {
+                coord,
+                rotation,
+                location,
+            }))
+            .unwrap();
+    }
+
+    fn show_frame(&mut self, frame: usize) {
+        if let Some(timeline) = &mut self.timeline {
+            timeline.frame = frame.min(timeline.entries.len().saturating_sub(1));
+            self.output_channel
+                .send(InteractionMessage::ShowFrame(timeline.frame))
+                .unwrap();
+        }
+    }
+
+    // steps an autoplaying replay and schedules the next repaint
+    fn advance_timeline(&mut self, ctx: &egui::Context) {
+        let Some(timeline) = &mut self.timeline else {
+            return;
+        };
+        if !timeline.playing {
+            return;
+        }
+        let now = ctx.input(|i| i.time);
+        let interval = 1.0 / timeline.speed as f64;
+        if now - timeline.last_step < interval {
+            ctx.request_repaint_after(Duration::from_secs_f64(interval));
+            return;
+        }
+        timeline.last_step = now;
+        let next = timeline.frame + 1;
+        if next >= timeline.entries.len() {
+            timeline.playing = false;
+            return;
+        }
+        self.show_frame(next);
+        ctx.request_repaint_after(Duration::from_secs_f64(interval));
+    }
+
+    fn timeline_controls(&mut self, ui: &mut egui::Ui) {
+        let Some(timeline) = &mut self.timeline else {
+            return;
+        };
+        let last = timeline.entries.len().saturating_sub(1);
+        let mut frame = timeline.frame;
+        ui.horizontal(|ui| {
+            let label = if timeline.playing { "Pause" } else { "Play" };
+            if ui.button(label).clicked() {
+                timeline.playing = !timeline.playing;
+                if timeline.playing && timeline.frame == last {
+                    frame = 0;
+                }
+            }
+            ui.add(
+                egui::Slider::new(&mut timeline.speed, 0.5..=20.0)
+                    .logarithmic(true)
+                    .text("moves/s"),
+            );
+            ui.separator();
+            ui.spacing_mut().slider_width = (ui.available_width() - 120.0).max(100.0);
+            ui.add(
+                egui::Slider::new(&mut frame, 0..=last)
+                    .custom_formatter(|n, _| format!("move {}", n as usize + 1)),
+            );
+        });
+        if frame != timeline.frame {
+            self.show_frame(frame);
+        }
+    }
+
+    fn move_list(&mut self, ui: &mut egui::Ui) {
+        let Some(timeline) = &self.timeline else {
+            return;
+        };
+        let mut clicked = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for (idx, entry) in timeline.entries.iter().enumerate() {
+                let meeple = entry
+                    .meeple
+                    .as_ref()
+                    .map(|target| format!(" {target:?}"))
+                    .unwrap_or_default();
+                let points: Vec<String> = entry
+                    .points
+                    .iter()
+                    .map(|(player, points)| format!("+{points} {player}"))
+                    .collect();
+                let text = format!(
+                    "{:>2}. {} {} {:?} {:?}{meeple} {}",
+                    idx + 1,
+                    entry.player,
+                    entry.tile,
+                    entry.coord,
+                    entry.rotation,
+                    points.join(" ")
+                );
+                let selected = idx == timeline.frame;
+                let response = ui.selectable_label(selected, egui::RichText::new(text).monospace());
+                if selected && timeline.playing {
+                    response.scroll_to_me(Some(egui::Align::Center));
+                }
+                if response.clicked() {
+                    clicked = Some(idx);
+                }
+            }
+        });
+        if let Some(idx) = clicked {
+            self.show_frame(idx);
+        }
+    }
+
+    // a human choosing a move only listens to clicks on the board
+    fn human_to_move(&self) -> bool {
+        self.render_state.as_ref().is_some_and(|state| {
+            !state.legal_placements.is_empty() || state.meeple_targets.is_some()
+        })
+    }
+
+    fn spectator_controls(&mut self, ui: &mut egui::Ui) {
+        let human_to_move = self.human_to_move();
+        let Some(spectator) = &mut self.spectator else {
+            return;
+        };
+        let mut commands = vec![];
+        ui.add_enabled_ui(!human_to_move, |ui| {
+            ui.horizontal(|ui| {
+                let label = if spectator.paused { "Resume" } else { "Pause" };
+                if ui.button(label).clicked() {
+                    spectator.paused = !spectator.paused;
+                    commands.push(SpectateCommand::TogglePause);
+                }
+                if ui
+                    .add_enabled(spectator.paused, egui::Button::new("Step"))
+                    .clicked()
+                {
+                    commands.push(SpectateCommand::Step);
+                }
+                let delay =
+                    egui::Slider::new(&mut spectator.delay_ms, 0..=5000).text("ms per move");
+                if ui.add(delay).changed() {
+                    commands.push(SpectateCommand::Delay(Duration::from_millis(
+                        spectator.delay_ms,
+                    )));
+                }
+                ui.separator();
+                spectator.seats.retain(|seat| {
+                    let take = ui.button(format!("Take over {seat}")).clicked();
+                    if take {
+                        commands.push(SpectateCommand::TakeOver(seat.clone()));
+                    }
+                    !take
+                });
+            })
+        });
+        // the game thread is gone once the game ends
+        for command in commands {
+            self.output_channel
+                .send(InteractionMessage::Spectate(command))
+                .ok();
+        }
+    }
+
+    fn handle_key(&mut self, key: egui::Key) {
+        let Some(state) = &mut self.render_state else {
+            return;
+        };
+        let placing_tile = !state.legal_placements.is_empty();
+        let step = match key {
+            egui::Key::ArrowUp => Some((-1, 0)),
+            egui::Key::ArrowDown => Some((1, 0)),
+            egui::Key::ArrowLeft => Some((0, -1)),
+            egui::Key::ArrowRight => Some((0, 1)),
+            _ => None,
+        };
+        if let (true, Some(step)) = (placing_tile, step) {
+            self.move_cursor(step);
+            return;
+        }
+        if let Some(timeline) = &mut self.timeline {
+            let frame = timeline.frame;
+            let frame = match key {
+                egui::Key::ArrowLeft => Some(frame.saturating_sub(1)),
+                egui::Key::ArrowRight => Some(frame + 1),
+                egui::Key::Home => Some(0),
+                egui::Key::End => Some(usize::MAX),
+                egui::Key::Space => {
+                    timeline.playing = !timeline.playing;
+                    None
+                }
+                _ => None,
+            };
+            if let Some(frame) = frame {
+                self.show_frame(frame);
+                return;
+            }
+        }
+        match key {
+            egui::Key::R => {
+                if let Some(preview_tile) = state.preview_tile.as_mut() {
+                    preview_tile.rotate_right()
+                }
+            }
+            egui::Key::Q => {
+                if let Some(preview_tile) = state.preview_tile.as_mut() {
+                    preview_tile.rotate_left()
+                }
+            }
+            egui::Key::Enter => {
+                let rotation = state
+                    .preview_tile
+                    .as_ref()
+                    .map(|tile| tile.rotation.clone());
+                if let (true, Some(cursor), Some(rotation)) = (placing_tile, self.cursor, rotation)
+                {
+                    self.send_click(cursor, TileClickTarget::Center, rotation);
+                }
+            }
+            egui::Key::H => self.show_shortcuts = !self.show_shortcuts,
+            egui::Key::Space => {
+                let human_to_move = placing_tile || state.meeple_targets.is_some();
+                if let (false, Some(spectator)) = (human_to_move, &mut self.spectator) {
+                    spectator.paused = !spectator.paused;
+                    self.output_channel
+                        .send(InteractionMessage::Spectate(SpectateCommand::TogglePause))
+                        .ok();
+                }
+            }
+            egui::Key::X => self
+                .output_channel
+                .send(InteractionMessage::CancelMeeple)
+                .unwrap(),
+            _ => {
+                let coord = state.meeple_targets.as_ref().map(|(coord, _)| *coord);
+                let target = MEEPLE_KEYS
+                    .iter()
+                    .find(|(meeple_key, _)| meeple_key == &key)
+                    .map(|(_, target)| target.clone());
+                if let (Some(coord), Some(target)) = (coord, target) {
+                    self.send_click(coord, target, Rotation::None);
+                }
+            }
+        }
+    }
+
+    // moves the cursor to the closest cell the tile fits in along `step`, starting
+    // next to the middle of the board
+    fn move_cursor(&mut self, step: (i32, i32)) {
+        let Some(state) = &self.render_state else {
+            return;
+        };
+        let cells = &state.legal_placements;
+        let Some(from) = self.cursor.filter(|cursor| cells.contains_key(cursor)) else {
+            self.cursor = cells
+                .keys()
+                .min_by_key(|(row, col)| (row.abs() + col.abs(), *row, *col))
+                .copied();
+            return;
+        };
+        let next = cells
+            .keys()
+            .filter_map(|coord| {
+                let (rows, cols) = (
+                    coord.0 as i32 - from.0 as i32,
+                    coord.1 as i32 - from.1 as i32,
+                );
+                let along = rows * step.0 + cols * step.1;
+                let across = (rows * step.1 - cols * step.0).abs();
+                (along > 0).then_some((along + 2 * across, *coord))
+            })
+            .min();
+        if let Some((_, coord)) = next {
+            self.cursor = Some(coord);
+        }
+    }
 }
 
 impl eframe::App for MyApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         while let Ok(message) = self.input_channel.try_recv() {
             match message {
-                RenderMessage::RefereeSync(state) => self.render_state = Some(state),
+                RenderMessage::RefereeSync(state) => {
+                    if let Some(cursor) = self.cursor {
+                        if !state.legal_placements.contains_key(&cursor) {
+                            self.cursor = None;
+                        }
+                    }
+                    self.render_state = Some(state);
+                }
+                RenderMessage::Log(line) => self.log.push(line),
+                RenderMessage::GameOver(results) => {
+                    self.results = Some(results);
+                    self.show_results = true;
+                }
+                RenderMessage::Stats(stats) => self.history.push(stats),
+                RenderMessage::Timeline(entries) => {
+                    self.timeline = Some(Timeline {
+                        entries,
+                        frame: 0,
+                        playing: false,
+                        speed: 2.0,
+                        last_step: 0.0,
+                    })
+                }
             }
         }
+        self.advance_timeline(ctx);
+
+        if self.spectator.is_some() {
+            egui::TopBottomPanel::bottom("spectator").show(ctx, |ui| self.spectator_controls(ui));
+        }
+        if self.timeline.is_some() {
+            egui::TopBottomPanel::bottom("timeline").show(ctx, |ui| self.timeline_controls(ui));
+            egui::SidePanel::left("moves")
+                .default_width(300.0)
+                .show(ctx, |ui| {
+                    ui.heading("Moves");
+                    self.move_list(ui);
+                });
+        }
+
+        egui::SidePanel::right("log")
+            .default_width(320.0)
+            .show(ctx, |ui| {
+                ui.heading("Log");
+                egui::ScrollArea::vertical()
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        for line in &self.log {
+                            ui.label(line);
+                        }
+                    });
+            });
+
+        let players = self
+            .render_state
+            .as_ref()
+            .map(|state| state.turn_order.clone())
+            .unwrap_or_default();
+        let frame = self.timeline.as_ref().map(|timeline| timeline.frame);
+        egui::Window::new("Score chart")
+            .default_width(500.0)
+            .open(&mut self.show_chart)
+            .show(ctx, |ui| score_chart(ui, &self.history, &players, frame));
+
+        if let Some(state) = &self.render_state {
+            egui::Window::new("Bag")
+                .default_width(360.0)
+                .open(&mut self.show_bag)
+                .show(ctx, |ui| bag_panel(ui, &state.bag));
+        }
+
+        egui::Window::new("Keyboard shortcuts")
+            .collapsible(false)
+            .resizable(false)
+            .open(&mut self.show_shortcuts)
+            .show(ctx, |ui| {
+                egui::Grid::new("shortcuts").striped(true).show(ui, |ui| {
+                    for (keys, action) in SHORTCUTS {
+                        ui.monospace(keys);
+                        ui.label(action);
+                        ui.end_row();
+                    }
+                });
+            });
+
+        if let Some(results) = &self.results {
+            egui::Window::new("Game over")
+                .collapsible(false)
+                .resizable(false)
+                .open(&mut self.show_results)
+                .show(ctx, |ui| {
+                    let best = results.iter().map(FinalScore::total).max().unwrap_or(0);
+                    let winners: Vec<String> = results
+                        .iter()
+                        .filter(|score| score.total() == best)
+                        .map(|score| format!("{} ({})", score.player, score.name))
+                        .collect();
+                    ui.strong(format!("Winners: {}", winners.join(", ")));
+                    egui::Grid::new("results").striped(true).show(ui, |ui| {
+                        for header in ["Player", "Bot", "Banked", "Endgame", "Total"] {
+                            ui.strong(header);
+                        }
+                        ui.end_row();
+                        for score in results {
+                            ui.label(score.player.to_string());
+                            ui.label(&score.name);
+                            ui.monospace(score.banked.to_string());
+                            ui.monospace(score.endgame.to_string());
+                            ui.monospace(score.total().to_string());
+                            ui.end_row();
+                        }
+                    });
+                });
+        }
 
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.heading("Crabcassone");
             ui.horizontal(|ui| {
-                ui.add(egui::Slider::new(&mut self.zoom, 40..=160).text("zoom"));
+                let mut zoom = self.zoom;
+                ui.add(egui::Slider::new(&mut zoom, MIN_ZOOM..=MAX_ZOOM).text("zoom"));
+                if zoom != self.zoom {
+                    self.zoom_around(Vec2::ZERO, zoom);
+                }
+                if ui.button("Fit board").clicked() {
+                    self.fit_requested = true;
+                }
                 ui.separator();
-                ui.label("Press R to rotate");
+                ui.label("Press H for keyboard shortcuts");
                 ui.separator();
-                ui.label("Press X to skip meeple placement");
+                ui.checkbox(&mut self.show_chart, "Score chart");
+                ui.checkbox(&mut self.show_bag, "Bag");
             });
             if let Some(state) = &self.render_state {
                 let score_map = state.board.as_overlay().get_standing_points();
@@ -118,99 +886,215 @@ impl eframe::App for MyApp {
                 ui.horizontal(|ui| {
                     ui.strong(format!("Current Player: {}", state.current_player));
                 });
+                if !state.hints.is_empty() {
+                    ui.horizontal_wrapped(|ui| {
+                        ui.label("Hints:");
+                        for hint in state.hints.iter().take(10) {
+                            let request = &hint.request;
+                            ui.monospace(format!(
+                                "{:?} {:?} {:?} {}",
+                                request.coord, request.rotation, request.meeple, hint.label
+                            ));
+                            ui.separator();
+                        }
+                    });
+                }
             }
 
             let events = ui.input(|i| i.events.clone());
             for event in &events {
-                #[allow(clippy::single_match)] // may expand
-                match event {
-                    egui::Event::Key {
-                        key,
-                        pressed,
-                        modifiers: _modifiers,
-                        repeat,
-                    } => {
-                        if *pressed && !repeat {
-                            match key {
-                                egui::Key::R => {
-                                    if let Some(preview_tile) = self
-                                        .render_state
-                                        .as_mut()
-                                        .and_then(|state| state.preview_tile.as_mut())
-                                    {
-                                        preview_tile.rotate_right()
-                                    }
-                                }
-                                egui::Key::End => self
-                                    .output_channel
-                                    .send(InteractionMessage::LastFrame)
-                                    .unwrap(),
-                                egui::Key::Home => self
-                                    .output_channel
-                                    .send(InteractionMessage::FirstFrame)
-                                    .unwrap(),
-                                egui::Key::ArrowLeft => self
-                                    .output_channel
-                                    .send(InteractionMessage::PreviousFrame)
-                                    .unwrap(),
-                                egui::Key::ArrowRight => self
-                                    .output_channel
-                                    .send(InteractionMessage::NextFrame)
-                                    .unwrap(),
-                                egui::Key::X => self
-                                    .output_channel
-                                    .send(InteractionMessage::CancelMeeple)
-                                    .unwrap(),
-                                _ => {}
-                            }
-                        }
-                    }
-                    _ => {}
+                if let egui::Event::Key {
+                    key,
+                    pressed: true,
+                    repeat: false,
+                    ..
+                } = event
+                {
+                    self.handle_key(*key);
                 }
             }
 
-            let grid = egui::Grid::new("some_unique_id").spacing(vec2(10.0, 10.0));
+            self.board_view(ui);
+        });
+    }
+}
 
-            if let Some(state) = &self.render_state {
-                let ((min_row, max_row), (min_col, max_col)) = state.board.boundaries();
-                egui::ScrollArea::both()
-                    .drag_to_scroll(true)
-                    .show(ui, |ui| {
-                        grid.show(ui, |ui| {
-                            for r in (min_row - 1)..(max_row + 1 + 1) {
-                                for c in (min_col - 1)..(max_col + 1 + 1) {
-                                    let coord = (r, c);
-                                    let response = ui
-                                        .push_id(coord, |ui| {
-                                            ui.add(render_tile::tile(
-                                                self.zoom as f32,
-                                                state.board.at(&coord),
-                                                coord,
-                                                if state.is_placing_meeple {
-                                                    &None
-                                                } else {
-                                                    &state.preview_tile
-                                                },
-                                                state.is_placing_meeple,
-                                                state.current_player.clone(),
-                                            ))
-                                        })
-                                        .inner;
-                                    response.ctx.data_mut(|map| {
-                                        let subtile_id = Id::new(TILE_CLICK_ID);
-                                        let maybe_val =
-                                            map.get_temp::<InteractionMessage>(subtile_id);
-                                        if let Some(val) = maybe_val {
-                                            self.output_channel.send(val).unwrap();
-                                        }
-                                        map.remove::<InteractionMessage>(subtile_id);
-                                    })
-                                }
-                                ui.end_row();
-                            }
-                        });
-                    });
-            };
+fn chart_color(player: &Player) -> egui::Color32 {
+    // the meeple colors, but black would vanish on the dark plot background
+    match player {
+        Player::White => egui::Color32::LIGHT_GRAY,
+        Player::Black => egui::Color32::from_rgb(90, 140, 255),
+    }
+}
+
+// scores and standing points above meeples in hand, one point per move. `frame` marks
+// the move shown when replaying
+fn score_chart(ui: &mut egui::Ui, history: &[TurnStats], players: &[Player], frame: Option<usize>) {
+    if history.is_empty() {
+        ui.label("No moves yet");
+        return;
+    }
+    let series = |value: &dyn Fn(&TurnStats) -> f64| -> PlotPoints {
+        history
+            .iter()
+            .enumerate()
+            .map(|(idx, stats)| [idx as f64 + 1.0, value(stats)])
+            .collect()
+    };
+    let marker = frame.map(|frame| VLine::new(frame as f64 + 1.0).color(egui::Color32::GOLD));
+    Plot::new("score_chart")
+        .height(220.0)
+        .legend(Legend::default())
+        .link_axis("chart_moves", true, false)
+        .include_y(0.0)
+        .show(ui, |plot_ui| {
+            for player in players {
+                let color = chart_color(player);
+                plot_ui.line(
+                    Line::new(series(&|stats| {
+                        *stats.scores.get(player).unwrap_or(&0) as f64
+                    }))
+                    .color(color)
+                    .name(format!("{player} score")),
+                );
+                plot_ui.line(
+                    Line::new(series(&|stats| {
+                        *stats.standing.get(player).unwrap_or(&0) as f64
+                    }))
+                    .color(color)
+                    .style(LineStyle::dashed_loose())
+                    .name(format!("{player} standing")),
+                );
+            }
+            if let Some(marker) = marker.clone() {
+                plot_ui.vline(marker);
+            }
+        });
+    Plot::new("meeple_chart")
+        .height(120.0)
+        .legend(Legend::default())
+        .link_axis("chart_moves", true, false)
+        .include_y(0.0)
+        .show(ui, |plot_ui| {
+            for player in players {
+                plot_ui.line(
+                    Line::new(series(&|stats| {
+                        *stats.meeples.get(player).unwrap_or(&0) as f64
+                    }))
+                    .color(chart_color(player))
+                    .name(format!("{player} meeples")),
+                );
+            }
+            if let Some(marker) = marker {
+                plot_ui.vline(marker);
+            }
         });
+}
+
+// a tile's most telling feature, as a single color for the minimap
+fn minimap_color(tile: &TileData) -> egui::Color32 {
+    let edges = [
+        TileClickTarget::Top,
+        TileClickTarget::Right,
+        TileClickTarget::Bottom,
+        TileClickTarget::Left,
+    ];
+    let feature = if tile.center_matches(&MiniTile::Monastery) {
+        MiniTile::Monastery
+    } else if edges.iter().any(|edge| tile.at(edge) == &MiniTile::City) {
+        MiniTile::City
+    } else {
+        MiniTile::Grass
+    };
+    feature.get_color()
+}
+
+// what a feature is worth and to whom, for the hover tooltip
+fn feature_tooltip(ui: &mut egui::Ui, feature: &FeatureResult) {
+    let (scorers, points) = feature.get_score(!feature.completed);
+    ui.strong(format!("{:?}", feature.feature));
+    ui.label(format!("Tiles: {}", feature.get_present_tiles().count()));
+    if feature.completed {
+        ui.label(format!("Completed, worth {points}"));
+    } else {
+        ui.label(format!("Incomplete, worth {points} at game end"));
+    }
+    let meeples: Vec<String> = feature
+        .get_meeples()
+        .into_iter()
+        .sorted()
+        .dedup_with_count()
+        .map(|(count, player)| format!("{player} x{count}"))
+        .collect();
+    if meeples.is_empty() {
+        ui.label("Meeples: none");
+    } else {
+        ui.label(format!("Meeples: {}", meeples.join(", ")));
     }
+    if !scorers.is_empty() {
+        let scorers: Vec<String> = scorers.iter().sorted().map(Player::to_string).collect();
+        ui.label(format!("Scores for: {}", scorers.join(" & ")));
+    }
+}
+
+// chance that one of the `completing` tiles is drawn next and within `draws` draws
+fn completion_odds(ui: &mut egui::Ui, completing: u32, bag: &[(TileData, u32)], draws: u32) {
+    let left: u32 = bag.iter().map(|(_, count)| count).sum();
+    if completing == 0 {
+        ui.label(format!("No single tile of the {left} left completes it"));
+        return;
+    }
+    // drawing without replacement, so one minus the chance of missing every time
+    let mut miss = 1.0;
+    for draw in 0..draws.min(left) {
+        miss *= (left - completing).saturating_sub(draw) as f64 / (left - draw) as f64;
+    }
+    ui.label(format!("{completing} of the {left} tiles left complete it"));
+    ui.label(format!(
+        "Next draw {:.0}%, next {draws} draws {:.0}%",
+        100.0 * completing as f64 / left as f64,
+        100.0 * (1.0 - miss)
+    ));
+}
+
+// remaining tile kinds with counts, drawn as thumbnails
+fn bag_panel(ui: &mut egui::Ui, bag: &[(TileData, u32)]) {
+    let left: u32 = bag.iter().map(|(_, count)| count).sum();
+    ui.strong(format!("{left} tiles left"));
+    ui.horizontal_wrapped(|ui| {
+        for (tile, count) in bag {
+            ui.vertical(|ui| {
+                let (rect, response) =
+                    ui.allocate_exact_size(Vec2::splat(40.0), egui::Sense::hover());
+                tile_art::paint_tile(ui.painter(), rect, tile, 1.0);
+                response.on_hover_text(tile.code());
+                ui.monospace(format!("x{count}"));
+            });
+        }
+    });
+}
+
+// shades an empty cell by the summed weight of its hints and labels the strongest
+fn paint_hints(ui: &egui::Ui, rect: &egui::Rect, coord: Coordinate, hints: &[MoveHint]) {
+    let here: Vec<&MoveHint> = hints
+        .iter()
+        .filter(|hint| hint.request.coord == coord)
+        .collect();
+    let Some(best) = here.iter().max_by(|a, b| a.weight.total_cmp(&b.weight)) else {
+        return;
+    };
+    let weight: f32 = here.iter().map(|hint| hint.weight).sum();
+    let painter = ui.painter();
+    painter.rect_filled(
+        *rect,
+        0.0,
+        egui::Color32::from_rgba_unmultiplied(40, 120, 255, (40.0 + weight.min(1.0) * 140.0) as u8),
+    );
+    painter.text(
+        rect.center(),
+        egui::Align2::CENTER_CENTER,
+        &best.label,
+        egui::FontId::monospace(14.0),
+        egui::Color32::WHITE,
+    );
 }
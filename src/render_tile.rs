@@ -1,15 +1,34 @@
 use egui::{
     epaint::{CircleShape, RectShape},
-    pos2, vec2, Color32, Id, Rect, Shape, Stroke,
+    pos2, vec2, Align2, Color32, FontId, Id, Rect, Shape, Stroke,
 };
 
 use crate::{
     board::{Coordinate, OCTAL_DELTAS},
-    render::{ClickMessage, InteractionMessage, TILE_CLICK_ID},
-    referee::Player, 
-    tile::{MiniTile, Rotation, TileClickTarget, TileData},
+    referee::Player,
+    render::{ClickMessage, InteractionMessage, FEATURE_HOVER_ID, MEEPLE_KEYS, TILE_CLICK_ID},
+    tile::{Rotation, TileClickTarget, TileData},
+    tile_art,
 };
 
+// Legality of moves on one cell, used to highlight it
+#[derive(Clone, Copy, Default)]
+pub struct CellHints<'a> {
+    /// Whether placements are being checked, off on an empty board where any cell goes
+    pub check_placement: bool,
+    /// Rotations the preview tile fits in here
+    pub legal_rotations: &'a [Rotation],
+    /// Where a meeple may go, only set on the tile just placed
+    pub meeple_targets: Option<&'a [TileClickTarget]>,
+    /// Whether the tile is part of the feature under the pointer
+    pub in_feature: bool,
+    /// Whether the keyboard cursor is on this cell, which previews the tile like hovering
+    pub cursor: bool,
+    /// Whether this tile was placed by the last move
+    pub last_placed: bool,
+}
+
+#[allow(clippy::too_many_arguments)]
 fn tile_ui(
     ui: &mut egui::Ui,
     size: f32,
@@ -18,6 +37,7 @@ fn tile_ui(
     preview_tile: &Option<TileData>,
     is_placing_meeple: bool,
     current_player: Player,
+    hints: CellHints,
 ) -> egui::Response {
     let (rect, response) = ui.allocate_exact_size(vec2(size, size), egui::Sense::click());
 
@@ -26,26 +46,10 @@ fn tile_ui(
     let center = rect.center();
     let mini_size = size / 3.0;
 
-    let get_color = |location: &Option<TileClickTarget>, tile: &TileData| {
-        if tile.secondary_center.is_some() && location == &Some(TileClickTarget::Center) {
-            return Color32::LIGHT_BLUE; // FIXME need to dual color here
-        }
-        if let Some(location) = location {
-            let tile_type = tile.at(location);
-            tile_type.get_color()
-        } else {
-            MiniTile::get_color(&MiniTile::Grass)
-        }
-    };
-
-    let emblem_rect = Rect::from_center_size(
-        pos2(center.x + -1_f32 * mini_size, center.y + -1_f32 * mini_size),
-        vec2(mini_size / 1.5, mini_size / 1.5),
-    );
     if ui.is_rect_visible(rect) {
         let visuals = ui.style().interact(&response);
 
-        let resolved_tile = tile.or(if response.hovered() {
+        let resolved_tile = tile.or(if response.hovered() || hints.cursor {
             preview_tile.as_ref()
         } else {
             None
@@ -53,6 +57,8 @@ fn tile_ui(
         let is_preview = tile.is_none();
 
         if let Some(tile) = resolved_tile {
+            let fade = if is_preview { 0.5 } else { 1.0 };
+            tile_art::paint_tile(ui.painter(), rect, tile, fade);
             let meeple_map = tile.get_meeple_locations();
             for mini_coord in minis {
                 let mini_rect = Rect::from_center_size(
@@ -64,50 +70,94 @@ fn tile_ui(
                 );
                 let target = TileClickTarget::from_octal(*mini_coord);
 
-                let mut color = get_color(&target, tile);
-                if is_preview {
-                    color = color.gamma_multiply(0.5);
-                }
-
-                rect_paint(ui, mini_rect, color);
-
                 if let Some(target) = target {
+                    if !is_preview {
+                        if let Some(hover_pos) = response.hover_pos() {
+                            if mini_rect.contains(hover_pos) {
+                                response.ctx.data_mut(|map| {
+                                    map.insert_temp(
+                                        Id::new(FEATURE_HOVER_ID),
+                                        (coord, target.clone()),
+                                    )
+                                });
+                            }
+                        }
+                    }
+                    let meeple_legal = hints
+                        .meeple_targets
+                        .map(|targets| targets.contains(&target))
+                        .unwrap_or(false);
                     if let Some(meeple_owner) = meeple_map.get(&target) {
                         meeple_paint(ui, mini_rect, meeple_owner.get_color());
-                    } else if let Some(click_pos) = response.interact_pointer_pos() {
-                        if is_placing_meeple && mini_rect.contains(click_pos) && response.clicked() {
-                            response.ctx.data_mut(|map| {
-                                let id = Id::new(TILE_CLICK_ID);
-                                map.insert_temp::<InteractionMessage>(
-                                    id,
-                                    InteractionMessage::Click(ClickMessage {
-                                        location: target,
-                                        rotation: Rotation::None,
-                                        coord,
-                                    }),
-                                );
-                            });
+                    } else if is_placing_meeple && meeple_legal {
+                        ui.painter().rect_stroke(
+                            mini_rect.shrink(1.0),
+                            0.0,
+                            Stroke::new(2.0, Color32::YELLOW),
+                        );
+                        if let Some(idx) = MEEPLE_KEYS.iter().position(|(_, t)| t == &target) {
+                            ui.painter().text(
+                                mini_rect.left_top() + vec2(2.0, 1.0),
+                                Align2::LEFT_TOP,
+                                (idx + 1).to_string(),
+                                FontId::proportional(mini_size * 0.35),
+                                Color32::BLACK,
+                            );
                         }
-                    } else if let Some(hover_pos) = response.hover_pos() {
-                        if is_placing_meeple && mini_rect.contains(hover_pos) {
-                            meeple_paint(ui, mini_rect, current_player.get_color());
+                        if let Some(click_pos) = response.interact_pointer_pos() {
+                            if mini_rect.contains(click_pos) && response.clicked() {
+                                response.ctx.data_mut(|map| {
+                                    let id = Id::new(TILE_CLICK_ID);
+                                    map.insert_temp::<InteractionMessage>(
+                                        id,
+                                        InteractionMessage::Click(ClickMessage {
+                                            location: target,
+                                            rotation: Rotation::None,
+                                            coord,
+                                        }),
+                                    );
+                                });
+                            }
+                        } else if let Some(hover_pos) = response.hover_pos() {
+                            if mini_rect.contains(hover_pos) {
+                                meeple_paint(ui, mini_rect, current_player.get_color());
+                            }
                         }
                     }
                 }
             }
-            let emblem_color = Color32::from_rgb(133, 50, 168);
-
-            if tile.has_emblem {
-                let emblem_color = if is_preview {
-                    emblem_color.gamma_multiply(0.5)
+            if hints.last_placed {
+                ui.painter()
+                    .rect_stroke(rect.shrink(1.5), 0.0, Stroke::new(3.0, Color32::WHITE));
+            }
+            if hints.cursor {
+                ui.painter().rect_stroke(
+                    rect.shrink(1.5),
+                    0.0,
+                    Stroke::new(3.0, Color32::LIGHT_BLUE),
+                );
+            }
+            if hints.in_feature {
+                ui.painter()
+                    .rect_stroke(rect.shrink(1.5), 0.0, Stroke::new(3.0, Color32::GOLD));
+            }
+            if is_preview && hints.check_placement {
+                // green if the preview fits in its current rotation
+                let fits = hints.legal_rotations.contains(&tile.rotation);
+                let tint = if fits {
+                    Color32::from_rgba_unmultiplied(0, 200, 0, 60)
                 } else {
-                    emblem_color
+                    Color32::from_rgba_unmultiplied(220, 0, 0, 60)
                 };
-                rect_paint(ui, emblem_rect, emblem_color);
+                ui.painter().rect_filled(rect, 0.0, tint);
             }
         } else {
-            ui.painter()
-                .rect(rect, 0.0, Color32::GRAY, visuals.bg_stroke);
+            let fill = if hints.legal_rotations.is_empty() {
+                Color32::GRAY
+            } else {
+                Color32::from_rgb(140, 190, 140)
+            };
+            ui.painter().rect(rect, 0.0, fill, visuals.bg_stroke);
         }
     }
 
@@ -135,12 +185,6 @@ fn tile_ui(
     response
 }
 
-fn rect_paint(ui: &egui::Ui, rect: Rect, color: Color32) {
-    if ui.is_rect_visible(rect) {
-        ui.painter().rect(rect, 0.0, color, Stroke::NONE);
-    }
-}
-
 fn meeple_paint(ui: &egui::Ui, rect: Rect, color: Color32) {
     if ui.is_rect_visible(rect) {
         let l = rect.height();
@@ -172,6 +216,18 @@ pub fn tile<'a>(
     preview_tile: &'a Option<TileData>,
     is_placing_meeple: bool,
     current_player: Player,
+    hints: CellHints<'a>,
 ) -> impl egui::Widget + 'a {
-    move |ui: &mut egui::Ui| tile_ui(ui, size, tile, coord, preview_tile, is_placing_meeple, current_player)
+    move |ui: &mut egui::Ui| {
+        tile_ui(
+            ui,
+            size,
+            tile,
+            coord,
+            preview_tile,
+            is_placing_meeple,
+            current_player,
+            hints,
+        )
+    }
 }
@@ -1,3 +1,5 @@
+use std::hash::{Hash, Hasher};
+
 use egui::Color32;
 use rustc_hash::FxHashMap;
 use serde::{Deserialize, Serialize};
@@ -38,6 +40,13 @@ pub static CARDINALS: [TileClickTarget; 4] = [
     TileClickTarget::Bottom,
 ];
 
+pub static ROTATIONS: [Rotation; 4] = [
+    Rotation::None,
+    Rotation::Left,
+    Rotation::Flip,
+    Rotation::Right,
+];
+
 #[derive(Clone, Debug, Default, Deserialize, Serialize, Hash, PartialEq, Eq)]
 pub enum Rotation {
     #[default]
@@ -137,6 +146,30 @@ impl TileData {
             && self.right() == other.right()
     }
 
+    /**
+     * @return true iff both tiles are the same kind of tile, ignoring rotation and meeples
+     */
+    pub fn is_same_type(&self, other: &TileData) -> bool {
+        self.has_emblem == other.has_emblem
+            && self.top == other.top
+            && self.left == other.left
+            && self.center == other.center
+            && self.secondary_center == other.secondary_center
+            && self.right == other.right
+            && self.bottom == other.bottom
+    }
+
+    // hashes what `is_same_type` compares
+    pub fn hash_type<H: Hasher>(&self, state: &mut H) {
+        self.has_emblem.hash(state);
+        self.top.hash(state);
+        self.left.hash(state);
+        self.center.hash(state);
+        self.secondary_center.hash(state);
+        self.right.hash(state);
+        self.bottom.hash(state);
+    }
+
     pub fn get_meeple_at(&self, target: &TileClickTarget) -> Option<Player> {
         self.meeple_locations
             .get(&self.rotation.rotate(target))
@@ -168,6 +201,28 @@ impl TileData {
     pub fn rotate_left(&mut self) {
         self.rotation = self.rotation.next_left();
     }
+    // edges clockwise from the top then the center, unrotated, e.g. "CRRG/R+C"
+    pub fn code(&self) -> String {
+        let mut code: String = [
+            &self.top,
+            &self.right,
+            &self.bottom,
+            &self.left,
+            &self.center,
+        ]
+        .iter()
+        .map(|mini| mini.letter())
+        .collect();
+        code.insert(4, '/');
+        if let Some(secondary) = &self.secondary_center {
+            code.push('+');
+            code.push(secondary.letter());
+        }
+        if self.has_emblem {
+            code.push('*');
+        }
+        code
+    }
 }
 
 impl From<TileDataBuilder> for TileData {
@@ -194,7 +249,7 @@ impl TileData {
 
     pub fn place_meeple(&mut self, target: &TileClickTarget, player: &Player) -> MessageResult<()> {
         let resolved_target = self.rotation.rotate(target);
-        if self.meeple_locations.get(&resolved_target).is_some() {
+        if self.meeple_locations.contains_key(&resolved_target) {
             return Err("Meeple preexists");
         }
         self.meeple_locations
@@ -213,7 +268,7 @@ impl TileData {
     }
 }
 
-#[derive(Clone, Hash, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[derive(Clone, Hash, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
 pub enum TileClickTarget {
     Top,
     Left,
@@ -235,7 +290,7 @@ impl TileClickTarget {
     }
 }
 
-#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub enum MiniTile {
     #[default]
     Grass,
@@ -249,6 +304,15 @@ impl MiniTile {
     pub fn is_traversable(&self) -> bool {
         matches!(&self, Self::Road | Self::City)
     }
+    pub fn letter(&self) -> char {
+        match self {
+            Self::Grass => 'G',
+            Self::Road => 'R',
+            Self::City => 'C',
+            Self::Monastery => 'M',
+            Self::Junction => 'J',
+        }
+    }
     pub fn get_color(&self) -> Color32 {
         match self {
             Self::Grass => Color32::from_rgb(0, 188, 84),
@@ -0,0 +1,290 @@
+use egui::{epaint::Mesh, pos2, vec2, Color32, Painter, Pos2, Rect, Shape, Stroke, Vec2};
+
+use crate::tile::{MiniTile, TileClickTarget, TileData};
+
+// Edges clockwise from the top, as (edge, start corner, end corner, inward normal) in
+// tile space where the tile spans [0, 1] on both axes
+const EDGES: [(TileClickTarget, Pos2, Pos2, Vec2); 4] = [
+    (
+        TileClickTarget::Top,
+        pos2(0.0, 0.0),
+        pos2(1.0, 0.0),
+        vec2(0.0, 1.0),
+    ),
+    (
+        TileClickTarget::Right,
+        pos2(1.0, 0.0),
+        pos2(1.0, 1.0),
+        vec2(-1.0, 0.0),
+    ),
+    (
+        TileClickTarget::Bottom,
+        pos2(1.0, 1.0),
+        pos2(0.0, 1.0),
+        vec2(0.0, -1.0),
+    ),
+    (
+        TileClickTarget::Left,
+        pos2(0.0, 1.0),
+        pos2(0.0, 0.0),
+        vec2(1.0, 0.0),
+    ),
+];
+
+const CENTER: Pos2 = pos2(0.5, 0.5);
+// how far a city wall bows into the tile, as the bezier control point's distance
+const WALL_DEPTH: f32 = 0.7;
+const CURVE_STEPS: usize = 12;
+
+const WALL_COLOR: Color32 = Color32::from_rgb(120, 72, 20);
+const ROAD_EDGE_COLOR: Color32 = Color32::from_rgb(90, 90, 90);
+const STONE_COLOR: Color32 = Color32::from_rgb(230, 220, 200);
+const EMBLEM_COLOR: Color32 = Color32::from_rgb(40, 70, 170);
+
+fn midpoint(start: Pos2, end: Pos2) -> Pos2 {
+    start + (end - start) / 2.0
+}
+
+// quadratic bezier from `start` to `end`, both included
+fn bezier(start: Pos2, control: Pos2, end: Pos2) -> Vec<Pos2> {
+    (0..=CURVE_STEPS)
+        .map(|step| {
+            let t = step as f32 / CURVE_STEPS as f32;
+            let a = start.to_vec2() * (1.0 - t) * (1.0 - t);
+            let b = control.to_vec2() * 2.0 * t * (1.0 - t);
+            let c = end.to_vec2() * t * t;
+            (a + b + c).to_pos2()
+        })
+        .collect()
+}
+
+// Draws `tile` into `rect` with egui shapes, `fade` scales every color (1.0 is opaque)
+pub fn paint_tile(painter: &Painter, rect: Rect, tile: &TileData, fade: f32) {
+    let size = rect.width();
+    let to_screen = |p: Pos2| rect.min + p.to_vec2() * size;
+    let color = |color: Color32| color.gamma_multiply(fade);
+
+    painter.rect_filled(rect, 0.0, color(MiniTile::Grass.get_color()));
+
+    let roads_connect = tile.center_matches(&MiniTile::Road);
+    if !roads_connect {
+        // dead ends run to the center, under whatever sits there
+        paint_roads(painter, tile, size, &to_screen, fade);
+    }
+
+    let city_anchor = paint_cities(painter, tile, size, &to_screen, fade);
+
+    if roads_connect {
+        paint_roads(painter, tile, size, &to_screen, fade);
+    }
+
+    match tile.center {
+        MiniTile::Monastery => paint_monastery(painter, size, &to_screen, fade),
+        MiniTile::Junction => {
+            let hub = Rect::from_center_size(to_screen(CENTER), vec2(size, size) * 0.16);
+            painter.rect(
+                hub,
+                size * 0.02,
+                color(STONE_COLOR),
+                Stroke::new(size * 0.02, color(ROAD_EDGE_COLOR)),
+            );
+        }
+        _ => {}
+    }
+
+    if let (true, Some(anchor)) = (tile.has_emblem, city_anchor) {
+        paint_emblem(painter, to_screen(anchor), size, fade);
+    }
+}
+
+fn paint_roads(
+    painter: &Painter,
+    tile: &TileData,
+    size: f32,
+    to_screen: &impl Fn(Pos2) -> Pos2,
+    fade: f32,
+) {
+    let ends: Vec<(Pos2, Vec2)> = EDGES
+        .iter()
+        .filter(|(edge, ..)| tile.at(edge) == &MiniTile::Road)
+        .map(|(_, start, end, inward)| (midpoint(*start, *end), *inward))
+        .collect();
+    if ends.is_empty() {
+        return;
+    }
+    let connected = tile.center_matches(&MiniTile::Road);
+    let paths: Vec<Vec<Pos2>> = if connected && ends.len() == 2 {
+        // a single road bending through the middle
+        vec![bezier(ends[0].0, CENTER, ends[1].0)]
+    } else {
+        ends.iter()
+            .map(|(mid, inward)| {
+                let stop = if tile.center == MiniTile::Grass {
+                    *mid + *inward * 0.35
+                } else {
+                    CENTER
+                };
+                vec![*mid, stop]
+            })
+            .collect()
+    };
+    // all the outlines first so crossings don't show seams
+    for (width, road_color) in [(0.13, ROAD_EDGE_COLOR), (0.09, MiniTile::Road.get_color())] {
+        for path in &paths {
+            let points = path.iter().map(|p| to_screen(*p)).collect();
+            painter.add(Shape::line(
+                points,
+                Stroke::new(size * width, road_color.gamma_multiply(fade)),
+            ));
+        }
+    }
+}
+
+// fills the city regions and their walls, returning where an emblem would go
+fn paint_cities(
+    painter: &Painter,
+    tile: &TileData,
+    size: f32,
+    to_screen: &impl Fn(Pos2) -> Pos2,
+    fade: f32,
+) -> Option<Pos2> {
+    let is_city: Vec<bool> = EDGES
+        .iter()
+        .map(|(edge, ..)| tile.at(edge) == &MiniTile::City)
+        .collect();
+    let count = is_city.iter().filter(|city| **city).count();
+    if count == 0 {
+        return None;
+    }
+    let fill = MiniTile::City.get_color().gamma_multiply(fade);
+    let wall = Stroke::new(size * 0.025, WALL_COLOR.gamma_multiply(fade));
+
+    if !tile.center_matches(&MiniTile::City) || count == 1 {
+        // separate caps along each city edge
+        let mut anchor = None;
+        for (idx, (_, start, end, inward)) in EDGES.iter().enumerate() {
+            if !is_city[idx] {
+                continue;
+            }
+            let mid = midpoint(*start, *end);
+            let curve = bezier(*end, mid + *inward * WALL_DEPTH * 0.85, *start);
+            let mut outline = vec![*start];
+            outline.extend(&curve[..curve.len() - 1]);
+            let inner = mid + *inward * 0.12;
+            fill_fan(painter, to_screen, inner, &outline, fill);
+            stroke_curve(painter, to_screen, &curve, wall);
+            anchor.get_or_insert(inner);
+        }
+        return anchor;
+    }
+
+    // one region through the middle: walk the border from a city edge, keeping city
+    // edges and replacing each run of other edges by a wall curving across the tile
+    let anchor = if count == 4 {
+        CENTER
+    } else {
+        let sum = EDGES
+            .iter()
+            .zip(&is_city)
+            .filter(|(_, city)| **city)
+            .map(|((_, start, end, _), _)| midpoint(*start, *end).to_vec2())
+            .fold(Vec2::ZERO, |acc, mid| acc + mid);
+        (sum / count as f32).to_pos2()
+    };
+    let first = (0..4)
+        .find(|idx| is_city[*idx] && !is_city[(idx + 3) % 4])
+        .unwrap_or(0);
+    let mut outline = vec![EDGES[first].1];
+    let mut walls = vec![];
+    let mut idx = 0;
+    while idx < 4 {
+        let edge = (first + idx) % 4;
+        if is_city[edge] {
+            outline.push(EDGES[edge].2);
+            idx += 1;
+            continue;
+        }
+        let run = (idx..4).take_while(|i| !is_city[(first + i) % 4]).count();
+        let start = EDGES[edge].1;
+        let end = EDGES[(edge + run - 1) % 4].2;
+        let control = match run {
+            1 => midpoint(start, end) + EDGES[edge].3 * WALL_DEPTH,
+            // across the diagonal, leaning slightly into the corner left out
+            _ => CENTER + (EDGES[edge].2 - CENTER) * 0.1,
+        };
+        let curve = bezier(start, control, end);
+        outline.extend(&curve[1..]);
+        walls.push(curve);
+        idx += run;
+    }
+    outline.pop(); // back at the first corner
+    fill_fan(painter, to_screen, anchor, &outline, fill);
+    for curve in &walls {
+        stroke_curve(painter, to_screen, curve, wall);
+    }
+    Some(anchor)
+}
+
+// `outline` has to be star shaped around `anchor`, which holds for the city shapes
+fn fill_fan(
+    painter: &Painter,
+    to_screen: &impl Fn(Pos2) -> Pos2,
+    anchor: Pos2,
+    outline: &[Pos2],
+    color: Color32,
+) {
+    let mut mesh = Mesh::default();
+    mesh.colored_vertex(to_screen(anchor), color);
+    for point in outline {
+        mesh.colored_vertex(to_screen(*point), color);
+    }
+    let n = outline.len() as u32;
+    for i in 0..n {
+        mesh.add_triangle(0, 1 + i, 1 + (i + 1) % n);
+    }
+    painter.add(Shape::mesh(mesh));
+}
+
+fn stroke_curve(
+    painter: &Painter,
+    to_screen: &impl Fn(Pos2) -> Pos2,
+    curve: &[Pos2],
+    stroke: Stroke,
+) {
+    painter.add(Shape::line(
+        curve.iter().map(|p| to_screen(*p)).collect(),
+        stroke,
+    ));
+}
+
+fn paint_monastery(painter: &Painter, size: f32, to_screen: &impl Fn(Pos2) -> Pos2, fade: f32) {
+    let outline = Stroke::new(size * 0.015, Color32::BLACK.gamma_multiply(fade));
+    let body = Rect::from_min_max(to_screen(pos2(0.36, 0.45)), to_screen(pos2(0.64, 0.68)));
+    painter.rect(body, 0.0, STONE_COLOR.gamma_multiply(fade), outline);
+    painter.add(Shape::convex_polygon(
+        vec![
+            to_screen(pos2(0.32, 0.46)),
+            to_screen(pos2(0.5, 0.3)),
+            to_screen(pos2(0.68, 0.46)),
+        ],
+        MiniTile::Monastery.get_color().gamma_multiply(fade),
+        outline,
+    ));
+    let door = Rect::from_min_max(to_screen(pos2(0.46, 0.56)), to_screen(pos2(0.54, 0.68)));
+    painter.rect_filled(door, 0.0, WALL_COLOR.gamma_multiply(fade));
+}
+
+fn paint_emblem(painter: &Painter, center: Pos2, size: f32, fade: f32) {
+    let (w, h) = (size * 0.14, size * 0.17);
+    painter.add(Shape::convex_polygon(
+        vec![
+            center + vec2(-w / 2.0, -h / 2.0),
+            center + vec2(w / 2.0, -h / 2.0),
+            center + vec2(w / 2.0, h * 0.1),
+            center + vec2(0.0, h / 2.0),
+            center + vec2(-w / 2.0, h * 0.1),
+        ],
+        EMBLEM_COLOR.gamma_multiply(fade),
+        Stroke::new(size * 0.015, Color32::WHITE.gamma_multiply(fade)),
+    ));
+}
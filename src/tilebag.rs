@@ -13,6 +13,7 @@ pub enum NextTileType {
     Empty,
 }
 
+#[allow(clippy::large_enum_variant)]
 #[enum_dispatch]
 pub enum TileBagEnum {
     LegalTileBag,
@@ -28,7 +29,8 @@ pub trait TileBag: Sync {
             let data = self.get_data_mut();
             let mut found = false;
             for i in 0..data.len() {
-                if data[i].matches_minis(elem) {
+                // edges alone don't tell emblem and plain variants apart
+                if data[i].is_same_type(elem) {
                     data.swap_remove(i);
                     found = true;
                     break;
@@ -90,6 +92,19 @@ pub trait TileBag: Sync {
     fn count_remaining(&self) -> u32 {
         self.get_data().len() as u32
     }
+
+    // remaining tiles grouped by kind, in order of first appearance
+    fn get_type_counts(&self) -> Vec<(TileData, u32)> {
+        let mut out: Vec<(TileData, u32)> = vec![];
+        for tile in self.get_data() {
+            if let Some((_, count)) = out.iter_mut().find(|(kind, _)| kind.is_same_type(tile)) {
+                *count += 1;
+            } else {
+                out.push((tile.clone(), 1));
+            }
+        }
+        out
+    }
 }
 
 pub struct LegalTileBag {
@@ -158,6 +173,14 @@ impl TileBag for ReplayTileBag {
 }
 
 impl LegalTileBag {
+    // full bag whose draw order only depends on `seed`, discards included
+    pub fn seeded(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            ..Default::default()
+        }
+    }
+
     fn from_data(data: Vec<TileData>, next: NextTileType) -> Self {
         Self {
             data,
@@ -441,16 +464,13 @@ impl Default for LegalTileBag {
             1
         ]);
 
-        data.push(
-            TileDataBuilder {
-                top: MiniTile::City,
-                left: MiniTile::Road,
-                right: MiniTile::Road,
-                center: MiniTile::Road,
-                ..Default::default()
-            }
-            .into(),
-        );
+        data.push(TileDataBuilder {
+            top: MiniTile::City,
+            left: MiniTile::Road,
+            right: MiniTile::Road,
+            center: MiniTile::Road,
+            ..Default::default()
+        });
         // for debug endgame
         // let out: Vec<TileDataBuilder> = data.into_iter().take(5).collect();
         let data: Vec<TileData> = data.into_iter().map(|builder| builder.into()).collect();
@@ -479,6 +499,46 @@ mod tests {
         assert_eq!(bag.count_remaining(), 71);
     }
 
+    #[test]
+    fn type_counts_cover_bag() {
+        let bag = LegalTileBag::default();
+        let counts = bag.get_type_counts();
+        assert_eq!(counts.len(), 24);
+        assert_eq!(counts.iter().map(|(_, ct)| ct).sum::<u32>(), 72);
+    }
+
+    #[test]
+    fn rig_takes_exact_type() {
+        let mut bag = LegalTileBag::default();
+        let (emblem, plain): (Vec<TileData>, Vec<TileData>) = bag
+            .get_type_counts()
+            .into_iter()
+            .map(|(tile, _)| tile)
+            .filter(|tile| tile.secondary_center.is_some() && tile.bottom() == &MiniTile::Road)
+            .partition(|tile| tile.has_emblem);
+        bag.rig(vec![emblem[0].clone()]);
+        let count = |tile: &TileData| {
+            bag.get_type_counts()
+                .into_iter()
+                .find(|(kind, _)| kind.is_same_type(tile))
+                .map(|(_, count)| count)
+        };
+        // the drawn tile is back on top of the bag, so both variants keep their count
+        assert_eq!(count(&plain[0]), Some(3));
+        assert_eq!(count(&emblem[0]), Some(2));
+        assert!(bag.peek().unwrap().is_same_type(&emblem[0]));
+    }
+
+    #[test]
+    fn seeded_bags_match() {
+        let mut left = LegalTileBag::seeded(7);
+        let mut right = LegalTileBag::seeded(7);
+        while let Some(tile) = left.pull() {
+            assert!(right.pull().unwrap().is_same_type(&tile));
+        }
+        assert!(right.pull().is_none());
+    }
+
     #[test]
     fn check_empties() {
         let mut bag = LegalTileBag::default();